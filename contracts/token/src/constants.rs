@@ -1,8 +1,39 @@
 // Ledger sequences (aka "Ledgers") is the unit of time in Stellar
 pub const DAY_IN_LEDGERS: u32 = 17280;
 
+// Defaults for the admin-configurable TTL policy
 pub const INSTANCE_TTL_FULL: u32 = 7 * DAY_IN_LEDGERS;
 pub const INSTANCE_TTL_THRESHOLD: u32 = INSTANCE_TTL_FULL - DAY_IN_LEDGERS;
 
 pub const BALANCE_TTL_FULL: u32 = 30 * DAY_IN_LEDGERS;
 pub const BALANCE_TTL_THRESHOLD: u32 = BALANCE_TTL_FULL - DAY_IN_LEDGERS;
+
+// Allowances of this amount are never decreased by spending
+pub const UNLIMITED_ALLOWANCE: i128 = i128::MAX;
+
+// Balances are `shares * index / REBASE_INDEX_PRECISION`
+pub const REBASE_INDEX_PRECISION: i128 = 1_000_000_000;
+
+// Maximum change of the rebase index per call in basis points
+pub const MAX_REBASE_CHANGE_BPS: i128 = 1_000;
+
+// Scales the reward-per-share accumulator to keep precision for small distributions
+pub const REWARD_MAGNITUDE: i128 = 10_i128.pow(36);
+
+pub const UNBONDING_PERIOD_LEDGERS: u32 = 7 * DAY_IN_LEDGERS;
+
+// Scales the staking reward-per-token accumulator
+pub const STAKING_REWARD_PRECISION: i128 = 10_i128.pow(18);
+
+// Expiry ledger of freezes that last until the account is unfrozen
+pub const FREEZE_INDEFINITELY: u32 = u32::MAX;
+
+// Maximum number of entries returned by a single page of an enumeration view, kept well
+// below the per-transaction ledger entry read limit
+pub const MAX_PAGE_SIZE: u32 = 50;
+
+// Number of addresses stored per persistent index entry
+pub const INDEX_CHUNK_SIZE: u32 = 100;
+
+// Number of ledgers a proposal can collect approvals for
+pub const PROPOSAL_LIFETIME_LEDGERS: u32 = 7 * DAY_IN_LEDGERS;
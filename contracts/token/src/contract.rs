@@ -1,47 +1,221 @@
 use crate::{
-    constants::{INSTANCE_TTL_FULL, INSTANCE_TTL_THRESHOLD},
-    storage::DataKey,
+    constants::{FREEZE_INDEFINITELY, UNBONDING_PERIOD_LEDGERS},
+    interfaces::TokenReceiverClient,
+    storage::{
+        AddressIndex, AdminAction, AdminCouncil, DataKey, HolderLimits, Htlc, Proposal, StakeInfo,
+        Stream, Subscription, TtlInfo, TtlPolicy,
+    },
     utils::{
-        admin::{has_admin, read_admin, write_admin},
-        allowance::{read_allowance, spend_allowance, write_allowance},
-        balance::{decrease_balance, increase_balance, read_balance},
+        admin::{has_admin, is_signer, read_council, require_admin, write_admin, write_council},
+        allowance::{
+            max_allowance_expiration, read_active_allowances, read_allowance, revoke_allowances,
+            spend_allowance, write_allowance,
+        },
+        balance::{extend_balance_ttl, read_balance, read_shares},
+        freeze::{
+            assert_account_not_frozen, freeze_remaining, read_frozen_amount, read_frozen_until,
+            write_frozen, write_frozen_amount,
+        },
+        holders::{read_holder_count, read_holder_limits, write_holder_limits},
+        htlc::{create_htlc, read_htlc, remove_htlc},
+        index::{index_len, read_index_page},
         metadata::{read_decimal, read_name, read_symbol, write_metadata},
+        proposal::{
+            assert_proposal_active, create_proposal, is_approved, read_proposal, write_proposal,
+        },
+        rebase::{read_index, write_index},
+        rewards::{
+            add_rewards, read_reward_token, record_withdrawal, withdrawable_rewards,
+            write_reward_excluded, write_reward_token,
+        },
+        rules::{read_transfer_rules, write_transfer_rules},
+        staking::{
+            accrue_pool, accrue_stake, read_stake, read_staking_pool, write_stake,
+            write_staking_pool,
+        },
+        stream::{create_stream, read_stream, remove_stream, streamed_amount, write_stream},
+        subscription::{
+            create_subscription, current_period, read_subscription, remove_subscription,
+            write_subscription,
+        },
+        supply::read_total_supply,
+        transfer::{assert_nonnegative_amount, move_balance, Movement},
+        ttl::{extend_account_ttl, extend_instance_ttl, read_ttl_policy, write_ttl_policy},
+        wrapper::{assert_fully_backed, read_underlying, write_underlying},
     },
 };
 use soroban_sdk::{
     contract, contractimpl,
     token::{self, Interface as _},
-    Address, Env, String,
+    Address, Bytes, BytesN, Env, IntoVal, String, Val, Vec,
 };
 use soroban_token_sdk::{metadata::TokenMetadata, TokenUtils};
 
-#[cfg(test)]
-use crate::storage::{AllowanceData, AllowanceDataKey};
+fn emit_custom_event(env: &Env, event_type: &str, admin: Address, account: Address) {
+    let topics = (event_type, admin, account);
+    let data = ();
+    env.events().publish(topics, data);
+}
 
-fn assert_nonnegative_amount(amount: i128) {
-    if amount < 0 {
-        panic!("Negative amount is not allowed: {}", amount);
-    }
+fn emit_proposal_event(env: &Env, event_type: &str, signer: Address, id: u32) {
+    let topics = (event_type, signer);
+    let data = id;
+    env.events().publish(topics, data);
 }
 
-fn assert_account_not_frozen(env: &Env, account: &Address) {
-    let key = DataKey::Frozen(account.clone());
-    if env
-        .storage()
-        .instance()
-        .get::<_, bool>(&key)
-        .unwrap_or(false)
-    {
-        panic!("Account is frozen");
-    }
+fn emit_subscription_event(
+    env: &Env,
+    event_type: &str,
+    payer: Address,
+    merchant: Address,
+    id: u32,
+    amount: i128,
+) {
+    let topics = (event_type, payer, merchant);
+    let data = (id, amount);
+    env.events().publish(topics, data);
 }
 
-fn emit_custom_event(env: &Env, event_type: &str, admin: Address, account: Address) {
-    let topics = (event_type, admin, account);
-    let data = ();
+fn emit_stream_event(
+    env: &Env,
+    event_type: &str,
+    sender: Address,
+    recipient: Address,
+    id: u32,
+    amount: i128,
+) {
+    let topics = (event_type, sender, recipient);
+    let data = (id, amount);
+    env.events().publish(topics, data);
+}
+
+fn emit_htlc_event<D>(env: &Env, event_type: &str, from: Address, to: Address, id: u32, data: D)
+where
+    (u32, D): IntoVal<Env, Val>,
+{
+    let topics = (event_type, from, to);
+    let data = (id, data);
     env.events().publish(topics, data);
 }
 
+fn execute_admin_action(env: &Env, admin: Address, action: AdminAction) {
+    extend_instance_ttl(env);
+
+    match action {
+        AdminAction::Mint(to, amount) => {
+            move_balance(env, Movement::Mint { issuer: admin, to }, amount);
+            assert_fully_backed(env);
+        }
+        AdminAction::SetAdmin(new_admin) => {
+            write_admin(env, &new_admin);
+
+            TokenUtils::new(env).events().set_admin(admin, new_admin);
+        }
+        AdminAction::FreezeAccount(account) => {
+            write_frozen(env, &account, Some(FREEZE_INDEFINITELY));
+
+            env.events()
+                .publish(("freeze_account", admin, account), FREEZE_INDEFINITELY);
+        }
+        AdminAction::FreezeAccountUntil(account, until) => {
+            write_frozen(env, &account, Some(until));
+
+            env.events()
+                .publish(("freeze_account", admin, account), until);
+        }
+        AdminAction::UnfreezeAccount(account) => {
+            write_frozen(env, &account, None);
+
+            emit_custom_event(env, "unfreeze_account", admin, account);
+        }
+        AdminAction::SetCouncil(signers, threshold) => {
+            write_council(env, &AdminCouncil { signers, threshold });
+
+            env.events()
+                .publish(("set_council", admin), read_council(env));
+        }
+        AdminAction::Rebase(index) => {
+            // A rebase would change the reserve a wrapped token must match 1:1
+            if read_underlying(env).is_some() {
+                panic!("Rebase is not supported for wrapped tokens");
+            }
+
+            let old_index = read_index(env);
+            write_index(env, index);
+
+            env.events().publish(("rebase", admin), (old_index, index));
+        }
+        AdminAction::SetRewardToken(token) => {
+            write_reward_token(env, &token);
+
+            env.events().publish(("set_reward_token", admin), token);
+        }
+        AdminAction::SetRewardExcluded(account, excluded) => {
+            let shares = read_shares(env, account.clone());
+            write_reward_excluded(env, account.clone(), excluded, shares);
+
+            env.events()
+                .publish(("set_reward_excluded", admin, account), excluded);
+        }
+        AdminAction::SetStakingRewardRate(rate) => {
+            assert_nonnegative_amount(rate);
+
+            let mut pool = read_staking_pool(env);
+            accrue_pool(env, &mut pool);
+            pool.reward_rate = rate;
+            write_staking_pool(env, &pool);
+
+            env.events()
+                .publish(("set_staking_reward_rate", admin), rate);
+        }
+        AdminAction::SetTtlPolicy(policy) => {
+            write_ttl_policy(env, &policy);
+            extend_instance_ttl(env);
+
+            env.events().publish(("set_ttl_policy", admin), policy);
+        }
+        AdminAction::SetTransferRules(rules) => {
+            write_transfer_rules(env, &rules);
+
+            env.events().publish(("set_transfer_rules", admin), rules);
+        }
+        AdminAction::SetHolderLimits(limits) => {
+            write_holder_limits(env, &limits);
+
+            env.events().publish(("set_holder_limits", admin), limits);
+        }
+        AdminAction::FreezeAmount(account, amount) => {
+            assert_nonnegative_amount(amount);
+
+            let frozen_amount = read_frozen_amount(env, &account)
+                .checked_add(amount)
+                .unwrap_or_else(|| panic!("Frozen amount overflow"));
+
+            // A partial freeze can't exceed the balance when it is set. Unfreezing is never
+            // blocked, even once a rebase has shrunk the balance below the frozen amount.
+            if frozen_amount > read_balance(env, account.clone()) {
+                panic!("Frozen amount exceeds balance");
+            }
+            write_frozen_amount(env, &account, frozen_amount);
+
+            env.events()
+                .publish(("freeze_amount", admin, account), amount);
+        }
+        AdminAction::UnfreezeAmount(account, amount) => {
+            assert_nonnegative_amount(amount);
+
+            let frozen_amount = read_frozen_amount(env, &account);
+            if amount > frozen_amount {
+                panic!("Unfreeze amount exceeds the frozen amount");
+            }
+            write_frozen_amount(env, &account, frozen_amount - amount);
+
+            env.events()
+                .publish(("unfreeze_amount", admin, account), amount);
+        }
+    }
+}
+
 #[contract]
 pub struct TokenContract;
 
@@ -69,78 +243,1020 @@ impl TokenContract {
         );
     }
 
+    pub fn initialize_wrapped(
+        env: Env,
+        admin: Address,
+        underlying: Address,
+        name: String,
+        symbol: String,
+    ) {
+        let decimal = token::Client::new(&env, &underlying).decimals();
+        Self::initialize(env.clone(), admin, decimal, name, symbol);
+
+        write_underlying(&env, &underlying);
+    }
+
     pub fn mint(env: Env, to: Address, amount: i128) {
-        let admin = read_admin(&env);
-        admin.require_auth();
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::Mint(to, amount));
+    }
+
+    pub fn set_admin(env: Env, new_admin: Address) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::SetAdmin(new_admin));
+    }
+
+    pub fn freeze_account(env: Env, account: Address) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::FreezeAccount(account));
+    }
+
+    pub fn freeze_account_until(env: Env, account: Address, until_ledger: u32) {
+        let admin = require_admin(&env);
+        execute_admin_action(
+            &env,
+            admin,
+            AdminAction::FreezeAccountUntil(account, until_ledger),
+        );
+    }
+
+    pub fn freeze_remaining(env: Env, account: Address) -> u32 {
+        freeze_remaining(&env, &account)
+    }
+
+    pub fn unfreeze_account(env: Env, account: Address) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::UnfreezeAccount(account));
+    }
+
+    pub fn freeze_amount(env: Env, account: Address, amount: i128) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::FreezeAmount(account, amount));
+    }
+
+    pub fn unfreeze_amount(env: Env, account: Address, amount: i128) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::UnfreezeAmount(account, amount));
+    }
+
+    pub fn frozen_amount(env: Env, account: Address) -> i128 {
+        read_frozen_amount(&env, &account)
+    }
+
+    pub fn set_council(env: Env, signers: Vec<Address>, threshold: u32) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::SetCouncil(signers, threshold));
+    }
+
+    pub fn rebase(env: Env, new_index: i128) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::Rebase(new_index));
+    }
+
+    pub fn rebase_index(env: Env) -> i128 {
+        read_index(&env)
+    }
+
+    pub fn shares_of(env: Env, account: Address) -> i128 {
+        read_shares(&env, account)
+    }
+
+    pub fn set_reward_token(env: Env, token: Address) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::SetRewardToken(token));
+    }
+
+    pub fn set_reward_excluded(env: Env, account: Address, excluded: bool) {
+        let admin = require_admin(&env);
+        execute_admin_action(
+            &env,
+            admin,
+            AdminAction::SetRewardExcluded(account, excluded),
+        );
+    }
+
+    pub fn set_staking_reward_rate(env: Env, rate: i128) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::SetStakingRewardRate(rate));
+    }
+
+    pub fn set_ttl_policy(env: Env, policy: TtlPolicy) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::SetTtlPolicy(policy));
+    }
+
+    pub fn ttl_policy(env: Env) -> TtlPolicy {
+        read_ttl_policy(&env)
+    }
+
+    pub fn set_transfer_rules(env: Env, rules: Option<Address>) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::SetTransferRules(rules));
+    }
+
+    pub fn transfer_rules(env: Env) -> Option<Address> {
+        read_transfer_rules(&env)
+    }
+
+    pub fn set_holder_limits(env: Env, limits: HolderLimits) {
+        let admin = require_admin(&env);
+        execute_admin_action(&env, admin, AdminAction::SetHolderLimits(limits));
+    }
+
+    pub fn holder_limits(env: Env) -> HolderLimits {
+        read_holder_limits(&env)
+    }
+
+    pub fn holder_count(env: Env) -> u32 {
+        read_holder_count(&env)
+    }
+
+    pub fn holders(env: Env, start: u32, limit: u32) -> Vec<(Address, i128)> {
+        let mut holders = Vec::new(&env);
+        for holder in read_index_page(&env, &AddressIndex::Holders, start, limit).iter() {
+            holders.push_back((holder.clone(), read_balance(&env, holder)));
+        }
+        holders
+    }
+
+    pub fn council(env: Env) -> AdminCouncil {
+        read_council(&env)
+    }
+
+    pub fn admins(env: Env) -> Vec<Address> {
+        read_council(&env).signers
+    }
+
+    // Each account is listed with the last ledger its freeze applies to. Expired freezes stay
+    // listed until the account is next used or unfrozen, so callers compare the expiry with the
+    // current ledger.
+    pub fn frozen_accounts(env: Env, start: u32, limit: u32) -> Vec<(Address, u32)> {
+        let mut accounts = Vec::new(&env);
+        for account in read_index_page(&env, &AddressIndex::Frozen, start, limit).iter() {
+            let until = read_frozen_until(&env, &account).unwrap();
+            accounts.push_back((account, until));
+        }
+        accounts
+    }
+
+    // Counts listed freezes, including expired ones that haven't been pruned yet
+    pub fn frozen_account_count(env: Env) -> u32 {
+        index_len(&env, &AddressIndex::Frozen)
+    }
+
+    pub fn propose(env: Env, proposer: Address, action: AdminAction) -> u32 {
+        proposer.require_auth();
+
+        if !is_signer(&env, &proposer) {
+            panic!("Not a council signer");
+        }
+
+        extend_instance_ttl(&env);
+
+        let (id, mut proposal) = create_proposal(&env, proposer.clone(), action);
+
+        emit_proposal_event(&env, "propose", proposer, id);
+
+        if is_approved(&env, &proposal) {
+            proposal.executed = true;
+            write_proposal(&env, id, &proposal);
+
+            execute_admin_action(&env, proposal.proposer, proposal.action);
+        }
+
+        id
+    }
+
+    pub fn approve_proposal(env: Env, signer: Address, id: u32) {
+        signer.require_auth();
+
+        if !is_signer(&env, &signer) {
+            panic!("Not a council signer");
+        }
+
+        extend_instance_ttl(&env);
+
+        let mut proposal = read_proposal(&env, id);
+
+        if proposal.executed {
+            panic!("Proposal already executed");
+        }
+
+        assert_proposal_active(&env, &proposal);
+
+        if proposal.approvals.contains(&signer) {
+            panic!("Proposal already approved by signer");
+        }
+
+        proposal.approvals.push_back(signer.clone());
+
+        emit_proposal_event(&env, "approve_proposal", signer, id);
+
+        if is_approved(&env, &proposal) {
+            proposal.executed = true;
+            write_proposal(&env, id, &proposal);
+
+            execute_admin_action(&env, proposal.proposer, proposal.action);
+        } else {
+            write_proposal(&env, id, &proposal);
+        }
+    }
+
+    pub fn proposal(env: Env, id: u32) -> Proposal {
+        read_proposal(&env, id)
+    }
+
+    pub fn increase_allowance(
+        env: Env,
+        from: Address,
+        spender: Address,
+        delta: i128,
+        expiration_ledger: u32,
+    ) {
+        from.require_auth();
+
+        assert_nonnegative_amount(delta);
+
+        extend_instance_ttl(&env);
+
+        let allowance = read_allowance(&env, from.clone(), spender.clone());
+        let amount = allowance
+            .amount
+            .checked_add(delta)
+            .unwrap_or_else(|| panic!("Allowance overflow"));
+
+        write_allowance(
+            &env,
+            from.clone(),
+            spender.clone(),
+            amount,
+            expiration_ledger,
+        );
+
+        TokenUtils::new(&env)
+            .events()
+            .approve(from, spender, amount, expiration_ledger);
+    }
+
+    pub fn decrease_allowance(env: Env, from: Address, spender: Address, delta: i128) {
+        from.require_auth();
+
+        assert_nonnegative_amount(delta);
+
+        extend_instance_ttl(&env);
+
+        let allowance = read_allowance(&env, from.clone(), spender.clone());
+        if allowance.amount < delta {
+            panic!("Allowance underflow");
+        }
+
+        let amount = allowance.amount - delta;
+        write_allowance(
+            &env,
+            from.clone(),
+            spender.clone(),
+            amount,
+            allowance.expiration_ledgers,
+        );
+
+        TokenUtils::new(&env)
+            .events()
+            .approve(from, spender, amount, allowance.expiration_ledgers);
+    }
+
+    pub fn max_allowance_expiration(env: Env) -> u32 {
+        max_allowance_expiration(&env)
+    }
+
+    pub fn allowances(env: Env, owner: Address) -> Vec<(Address, i128, u32)> {
+        extend_instance_ttl(&env);
+
+        read_active_allowances(&env, owner)
+    }
+
+    pub fn revoke_all(env: Env, owner: Address) {
+        owner.require_auth();
+
+        extend_instance_ttl(&env);
+
+        for (spender, _, expiration_ledgers) in revoke_allowances(&env, owner.clone()) {
+            TokenUtils::new(&env)
+                .events()
+                .approve(owner.clone(), spender, 0, expiration_ledgers);
+        }
+    }
+
+    pub fn approve_if(
+        env: Env,
+        from: Address,
+        spender: Address,
+        expected_current: i128,
+        new_amount: i128,
+        expiration_ledger: u32,
+    ) {
+        from.require_auth();
+
+        assert_nonnegative_amount(new_amount);
+
+        extend_instance_ttl(&env);
+
+        if read_allowance(&env, from.clone(), spender.clone()).amount != expected_current {
+            panic!("Current allowance does not match the expected amount");
+        }
+
+        write_allowance(
+            &env,
+            from.clone(),
+            spender.clone(),
+            new_amount,
+            expiration_ledger,
+        );
+
+        TokenUtils::new(&env)
+            .events()
+            .approve(from, spender, new_amount, expiration_ledger);
+    }
+
+    pub fn subscribe(
+        env: Env,
+        payer: Address,
+        merchant: Address,
+        amount_per_period: i128,
+        period_ledgers: u32,
+        max_periods: u32,
+    ) -> u32 {
+        payer.require_auth();
+
+        assert_nonnegative_amount(amount_per_period);
+
+        if period_ledgers == 0 {
+            panic!("Period must be at least one ledger");
+        }
+
+        if max_periods == 0 {
+            panic!("Subscription must have at least one period");
+        }
+
+        extend_instance_ttl(&env);
+
+        let id = create_subscription(
+            &env,
+            &Subscription {
+                payer: payer.clone(),
+                merchant: merchant.clone(),
+                amount_per_period,
+                period_ledgers,
+                max_periods,
+                start_ledger: env.ledger().sequence(),
+                collected_periods: 0,
+                next_period: 0,
+            },
+        );
+
+        emit_subscription_event(&env, "subscribe", payer, merchant, id, amount_per_period);
+
+        id
+    }
+
+    pub fn collect(env: Env, subscription_id: u32) {
+        let mut subscription = read_subscription(&env, subscription_id);
+        subscription.merchant.require_auth();
+
+        extend_instance_ttl(&env);
+
+        let period = current_period(&env, &subscription);
+        if period < subscription.next_period {
+            panic!("Subscription already collected for this period");
+        }
+
+        let payer = subscription.payer.clone();
+        let merchant = subscription.merchant.clone();
+        let amount = subscription.amount_per_period;
+
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: payer.clone(),
+                to: merchant.clone(),
+            },
+            amount,
+        );
+
+        subscription.collected_periods += 1;
+        subscription.next_period = period + 1;
+        if subscription.collected_periods < subscription.max_periods {
+            write_subscription(&env, subscription_id, &subscription);
+        } else {
+            remove_subscription(&env, subscription_id);
+        }
+
+        emit_subscription_event(&env, "collect", payer, merchant, subscription_id, amount);
+    }
+
+    pub fn cancel_subscription(env: Env, subscription_id: u32) {
+        let subscription = read_subscription(&env, subscription_id);
+        subscription.payer.require_auth();
+
+        extend_instance_ttl(&env);
+
+        remove_subscription(&env, subscription_id);
+
+        emit_subscription_event(
+            &env,
+            "cancel_subscription",
+            subscription.payer,
+            subscription.merchant,
+            subscription_id,
+            subscription.amount_per_period,
+        );
+    }
+
+    pub fn subscription(env: Env, subscription_id: u32) -> Subscription {
+        read_subscription(&env, subscription_id)
+    }
+
+    pub fn create_stream(
+        env: Env,
+        sender: Address,
+        recipient: Address,
+        deposit: i128,
+        start_ledger: u32,
+        stop_ledger: u32,
+    ) -> u32 {
+        sender.require_auth();
+
+        assert_nonnegative_amount(deposit);
+
+        if start_ledger < env.ledger().sequence() {
+            panic!("Stream can't start in the past");
+        }
+
+        if stop_ledger <= start_ledger {
+            panic!("Stream must stop after it starts");
+        }
+
+        extend_instance_ttl(&env);
+
+        let escrow = env.current_contract_address();
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: sender.clone(),
+                to: escrow.clone(),
+            },
+            deposit,
+        );
+
+        let id = create_stream(
+            &env,
+            &Stream {
+                sender: sender.clone(),
+                recipient: recipient.clone(),
+                deposit,
+                start_ledger,
+                stop_ledger,
+                withdrawn: 0,
+            },
+        );
+
+        emit_stream_event(&env, "create_stream", sender, recipient, id, deposit);
+
+        id
+    }
+
+    pub fn withdraw_from_stream(env: Env, stream_id: u32, amount: i128) {
+        let mut stream = read_stream(&env, stream_id);
+        stream.recipient.require_auth();
 
         assert_nonnegative_amount(amount);
+        assert_account_not_frozen(&env, &stream.recipient);
+
+        extend_instance_ttl(&env);
 
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL);
+        if streamed_amount(&env, &stream) - stream.withdrawn < amount {
+            panic!("Insufficient stream balance");
+        }
 
-        increase_balance(&env, to.clone(), amount);
+        let escrow = env.current_contract_address();
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: escrow.clone(),
+                to: stream.recipient.clone(),
+            },
+            amount,
+        );
 
-        TokenUtils::new(&env).events().mint(admin, to, amount);
+        stream.withdrawn += amount;
+        if stream.withdrawn < stream.deposit {
+            write_stream(&env, stream_id, &stream);
+        } else {
+            remove_stream(&env, stream_id);
+        }
+
+        emit_stream_event(
+            &env,
+            "withdraw_from_stream",
+            stream.sender,
+            stream.recipient,
+            stream_id,
+            amount,
+        );
     }
 
-    pub fn set_admin(env: Env, new_admin: Address) {
-        let admin = read_admin(&env);
-        admin.require_auth();
+    pub fn cancel_stream(env: Env, stream_id: u32) {
+        let stream = read_stream(&env, stream_id);
+        stream.sender.require_auth();
+
+        extend_instance_ttl(&env);
+
+        let streamed = streamed_amount(&env, &stream);
+        let recipient_amount = streamed - stream.withdrawn;
+        let sender_amount = stream.deposit - streamed;
+
+        let escrow = env.current_contract_address();
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: escrow.clone(),
+                to: stream.recipient.clone(),
+            },
+            recipient_amount,
+        );
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: escrow,
+                to: stream.sender.clone(),
+            },
+            sender_amount,
+        );
 
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL);
+        remove_stream(&env, stream_id);
 
-        write_admin(&env, &new_admin);
+        emit_stream_event(
+            &env,
+            "cancel_stream",
+            stream.sender,
+            stream.recipient,
+            stream_id,
+            sender_amount,
+        );
+    }
 
-        TokenUtils::new(&env).events().set_admin(admin, new_admin);
+    pub fn stream(env: Env, stream_id: u32) -> Stream {
+        read_stream(&env, stream_id)
     }
 
-    pub fn freeze_account(env: Env, account: Address) {
-        let admin = read_admin(&env);
-        admin.require_auth();
+    pub fn stream_balance(env: Env, stream_id: u32, who: Address) -> i128 {
+        let stream = read_stream(&env, stream_id);
+        let streamed = streamed_amount(&env, &stream);
 
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL);
+        if who == stream.recipient {
+            streamed - stream.withdrawn
+        } else if who == stream.sender {
+            stream.deposit - streamed
+        } else {
+            0
+        }
+    }
 
-        let key = DataKey::Frozen(account.clone());
-        env.storage().instance().set(&key, &true);
+    pub fn transfer_with_memo(env: Env, from: Address, to: Address, amount: i128, memo: u64) {
+        Self::transfer(env.clone(), from.clone(), to.clone(), amount);
 
-        emit_custom_event(&env, "freeze_account", admin, account);
+        let topics = ("transfer_with_memo", from, to);
+        let data = (amount, memo);
+        env.events().publish(topics, data);
     }
 
-    pub fn unfreeze_account(env: Env, account: Address) {
-        let admin = read_admin(&env);
-        admin.require_auth();
+    pub fn transfer_and_call(
+        env: Env,
+        from: Address,
+        to_contract: Address,
+        amount: i128,
+        data: Bytes,
+    ) {
+        Self::transfer(env.clone(), from.clone(), to_contract.clone(), amount);
+
+        let receiver = TokenReceiverClient::new(&env, &to_contract);
+        if !receiver.on_token_received(&env.current_contract_address(), &from, &amount, &data) {
+            panic!("Transfer rejected by receiver");
+        }
+    }
+
+    pub fn total_supply(env: Env) -> i128 {
+        read_total_supply(&env)
+    }
+
+    pub fn underlying(env: Env) -> Option<Address> {
+        read_underlying(&env)
+    }
+
+    pub fn deposit(env: Env, from: Address, amount: i128) {
+        from.require_auth();
+
+        assert_nonnegative_amount(amount);
+
+        let underlying = read_underlying(&env).unwrap_or_else(|| panic!("Not a wrapped token"));
+
+        extend_instance_ttl(&env);
+
+        let wrapper = env.current_contract_address();
+        token::Client::new(&env, &underlying).transfer(&from, &wrapper, &amount);
+
+        move_balance(
+            &env,
+            Movement::Mint {
+                issuer: wrapper,
+                to: from,
+            },
+            amount,
+        );
+        assert_fully_backed(&env);
+    }
 
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL);
+    pub fn withdraw(env: Env, to: Address, amount: i128) {
+        to.require_auth();
 
-        let key = DataKey::Frozen(account.clone());
-        env.storage().instance().remove(&key);
+        let underlying = read_underlying(&env).unwrap_or_else(|| panic!("Not a wrapped token"));
 
-        emit_custom_event(&env, "unfreeze_account", admin, account);
+        move_balance(&env, Movement::Burn { from: to.clone() }, amount);
+
+        let wrapper = env.current_contract_address();
+        token::Client::new(&env, &underlying).transfer(&wrapper, &to, &amount);
+        assert_fully_backed(&env);
+    }
+
+    pub fn reward_token(env: Env) -> Address {
+        read_reward_token(&env).unwrap_or(env.current_contract_address())
     }
 
-    #[cfg(test)]
-    pub fn test_get_allowance(env: Env, from: Address, spender: Address) -> Option<AllowanceData> {
-        let key = DataKey::Allowance(AllowanceDataKey {
-            owner: from,
-            spender,
-        });
-        env.storage().temporary().get::<_, AllowanceData>(&key)
+    pub fn distribute(env: Env, from: Address, amount: i128) {
+        from.require_auth();
+
+        assert_nonnegative_amount(amount);
+
+        extend_instance_ttl(&env);
+
+        let pool = env.current_contract_address();
+        let reward_token = Self::reward_token(env.clone());
+        if reward_token == pool {
+            move_balance(
+                &env,
+                Movement::Transfer {
+                    from: from.clone(),
+                    to: pool.clone(),
+                },
+                amount,
+            );
+        } else {
+            token::Client::new(&env, &reward_token).transfer(&from, &pool, &amount);
+        }
+
+        add_rewards(&env, amount);
+
+        env.events().publish(("distribute", from), amount);
+    }
+
+    pub fn withdrawable_rewards(env: Env, account: Address) -> i128 {
+        let shares = read_shares(&env, account.clone());
+        withdrawable_rewards(&env, account, shares)
+    }
+
+    pub fn claim_rewards(env: Env, account: Address) -> i128 {
+        account.require_auth();
+
+        assert_account_not_frozen(&env, &account);
+
+        extend_instance_ttl(&env);
+
+        let amount = Self::withdrawable_rewards(env.clone(), account.clone());
+        if amount == 0 {
+            return 0;
+        }
+
+        record_withdrawal(&env, account.clone(), amount);
+
+        let pool = env.current_contract_address();
+        let reward_token = Self::reward_token(env.clone());
+        if reward_token == pool {
+            move_balance(
+                &env,
+                Movement::Transfer {
+                    from: pool.clone(),
+                    to: account.clone(),
+                },
+                amount,
+            );
+        } else {
+            token::Client::new(&env, &reward_token).transfer(&pool, &account, &amount);
+        }
+
+        env.events().publish(("claim_rewards", account), amount);
+
+        amount
+    }
+
+    pub fn fund_staking_rewards(env: Env, from: Address, amount: i128) {
+        from.require_auth();
+
+        assert_nonnegative_amount(amount);
+
+        extend_instance_ttl(&env);
+
+        let escrow = env.current_contract_address();
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: from.clone(),
+                to: escrow.clone(),
+            },
+            amount,
+        );
+
+        let mut pool = read_staking_pool(&env);
+        accrue_pool(&env, &mut pool);
+        pool.reward_reserve += amount;
+        write_staking_pool(&env, &pool);
+        env.events().publish(("fund_staking_rewards", from), amount);
+    }
+
+    pub fn stake(env: Env, from: Address, amount: i128) {
+        from.require_auth();
+
+        assert_nonnegative_amount(amount);
+
+        extend_instance_ttl(&env);
+
+        let escrow = env.current_contract_address();
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: from.clone(),
+                to: escrow.clone(),
+            },
+            amount,
+        );
+
+        let mut pool = read_staking_pool(&env);
+        accrue_pool(&env, &mut pool);
+        let mut stake = read_stake(&env, from.clone());
+        accrue_stake(&env, &pool, &mut stake);
+
+        stake.amount += amount;
+        pool.total_staked += amount;
+        write_stake(&env, from.clone(), &stake);
+        write_staking_pool(&env, &pool);
+        env.events().publish(("stake", from), amount);
+    }
+
+    pub fn unstake(env: Env, from: Address, amount: i128) {
+        from.require_auth();
+
+        assert_nonnegative_amount(amount);
+
+        extend_instance_ttl(&env);
+
+        let mut pool = read_staking_pool(&env);
+        accrue_pool(&env, &mut pool);
+        let mut stake = read_stake(&env, from.clone());
+        accrue_stake(&env, &pool, &mut stake);
+
+        if stake.amount < amount {
+            panic!("Insufficient staked balance");
+        }
+
+        // Unstaking again restarts the unbonding period for the whole unbonding amount
+        stake.amount -= amount;
+        stake.unbonding_amount += amount;
+        stake.unbonding_until_ledger = env.ledger().sequence() + UNBONDING_PERIOD_LEDGERS;
+        pool.total_staked -= amount;
+        write_stake(&env, from.clone(), &stake);
+        write_staking_pool(&env, &pool);
+
+        env.events()
+            .publish(("unstake", from), (amount, stake.unbonding_until_ledger));
+    }
+
+    pub fn withdraw_unstaked(env: Env, from: Address) -> i128 {
+        from.require_auth();
+
+        assert_account_not_frozen(&env, &from);
+
+        extend_instance_ttl(&env);
+
+        let mut stake = read_stake(&env, from.clone());
+        if env.ledger().sequence() < stake.unbonding_until_ledger {
+            panic!("Unbonding period has not ended");
+        }
+
+        let amount = stake.unbonding_amount;
+        stake.unbonding_amount = 0;
+        write_stake(&env, from.clone(), &stake);
+
+        let escrow = env.current_contract_address();
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: escrow.clone(),
+                to: from.clone(),
+            },
+            amount,
+        );
+
+        env.events().publish(("withdraw_unstaked", from), amount);
+
+        amount
+    }
+
+    pub fn claim_staking_rewards(env: Env, from: Address) -> i128 {
+        from.require_auth();
+
+        assert_account_not_frozen(&env, &from);
+
+        extend_instance_ttl(&env);
+
+        let mut pool = read_staking_pool(&env);
+        accrue_pool(&env, &mut pool);
+        let mut stake = read_stake(&env, from.clone());
+        accrue_stake(&env, &pool, &mut stake);
+
+        let amount = stake.rewards;
+        stake.rewards = 0;
+        write_stake(&env, from.clone(), &stake);
+        write_staking_pool(&env, &pool);
+
+        let escrow = env.current_contract_address();
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: escrow.clone(),
+                to: from.clone(),
+            },
+            amount,
+        );
+
+        env.events()
+            .publish(("claim_staking_rewards", from), amount);
+
+        amount
+    }
+
+    pub fn staked_balance(env: Env, account: Address) -> i128 {
+        read_stake(&env, account).amount
+    }
+
+    pub fn stake_info(env: Env, account: Address) -> StakeInfo {
+        let mut pool = read_staking_pool(&env);
+        accrue_pool(&env, &mut pool);
+        let mut stake = read_stake(&env, account);
+        accrue_stake(&env, &pool, &mut stake);
+
+        stake
+    }
+
+    // Staked and unbonding tokens can't be transferred but still count for governance
+    pub fn voting_power(env: Env, account: Address) -> i128 {
+        let stake = read_stake(&env, account.clone());
+        read_balance(&env, account) + stake.amount + stake.unbonding_amount
+    }
+
+    pub fn extend_instance_ttl(env: Env) {
+        extend_instance_ttl(&env);
+    }
+
+    pub fn extend_balance_ttl(env: Env, accounts: Vec<Address>) {
+        extend_instance_ttl(&env);
+
+        for account in accounts {
+            extend_account_ttl(&env, &account);
+            extend_balance_ttl(&env, account);
+        }
+    }
+
+    pub fn ttl_info(env: Env, account: Address) -> TtlInfo {
+        TtlInfo {
+            has_balance: env.storage().persistent().has(&DataKey::Balance(account)),
+            policy: read_ttl_policy(&env),
+            max_ttl: env.storage().max_ttl(),
+        }
+    }
+
+    pub fn lock_htlc(
+        env: Env,
+        from: Address,
+        to: Address,
+        amount: i128,
+        hashlock: BytesN<32>,
+        timeout_ledger: u32,
+    ) -> u32 {
+        from.require_auth();
+
+        assert_nonnegative_amount(amount);
+
+        if timeout_ledger <= env.ledger().sequence() {
+            panic!("Timeout must be in the future");
+        }
+
+        extend_instance_ttl(&env);
+
+        let escrow = env.current_contract_address();
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: from.clone(),
+                to: escrow.clone(),
+            },
+            amount,
+        );
+
+        let id = create_htlc(
+            &env,
+            &Htlc {
+                from: from.clone(),
+                to: to.clone(),
+                amount,
+                hashlock,
+                timeout_ledger,
+            },
+        );
+
+        emit_htlc_event(&env, "lock_htlc", from, to, id, amount);
+
+        id
+    }
+
+    pub fn redeem_htlc(env: Env, htlc_id: u32, preimage: Bytes) {
+        let htlc = read_htlc(&env, htlc_id);
+
+        assert_account_not_frozen(&env, &htlc.from);
+        assert_account_not_frozen(&env, &htlc.to);
+
+        if env.ledger().sequence() >= htlc.timeout_ledger {
+            panic!("HTLC has timed out");
+        }
+
+        if env.crypto().sha256(&preimage).to_bytes() != htlc.hashlock {
+            panic!("Preimage does not match hashlock");
+        }
+
+        extend_instance_ttl(&env);
+
+        let escrow = env.current_contract_address();
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: escrow.clone(),
+                to: htlc.to.clone(),
+            },
+            htlc.amount,
+        );
+
+        remove_htlc(&env, htlc_id);
+        emit_htlc_event(&env, "redeem_htlc", htlc.from, htlc.to, htlc_id, preimage);
+    }
+
+    pub fn refund_htlc(env: Env, htlc_id: u32) {
+        let htlc = read_htlc(&env, htlc_id);
+
+        assert_account_not_frozen(&env, &htlc.from);
+
+        if env.ledger().sequence() < htlc.timeout_ledger {
+            panic!("HTLC has not timed out yet");
+        }
+
+        extend_instance_ttl(&env);
+
+        let escrow = env.current_contract_address();
+        move_balance(
+            &env,
+            Movement::Transfer {
+                from: escrow.clone(),
+                to: htlc.from.clone(),
+            },
+            htlc.amount,
+        );
+
+        remove_htlc(&env, htlc_id);
+        emit_htlc_event(
+            &env,
+            "refund_htlc",
+            htlc.from,
+            htlc.to,
+            htlc_id,
+            htlc.amount,
+        );
+    }
+
+    pub fn htlc(env: Env, htlc_id: u32) -> Htlc {
+        read_htlc(&env, htlc_id)
     }
 }
 
 #[contractimpl]
 impl token::Interface for TokenContract {
     fn allowance(env: Env, from: Address, spender: Address) -> i128 {
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL);
+        extend_instance_ttl(&env);
 
         read_allowance(&env, from, spender).amount
     }
@@ -150,9 +1266,7 @@ impl token::Interface for TokenContract {
 
         assert_nonnegative_amount(amount);
 
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL);
+        extend_instance_ttl(&env);
 
         write_allowance(
             &env,
@@ -168,9 +1282,7 @@ impl token::Interface for TokenContract {
     }
 
     fn balance(env: Env, id: Address) -> i128 {
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL);
+        extend_instance_ttl(&env);
 
         read_balance(&env, id)
     }
@@ -178,65 +1290,31 @@ impl token::Interface for TokenContract {
     fn transfer(env: Env, from: Address, to: Address, amount: i128) {
         from.require_auth();
 
-        assert_nonnegative_amount(amount);
-        assert_account_not_frozen(&env, &from);
-
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL);
-
-        decrease_balance(&env, from.clone(), amount);
-        increase_balance(&env, to.clone(), amount);
-
-        TokenUtils::new(&env).events().transfer(from, to, amount);
+        move_balance(&env, Movement::Transfer { from, to }, amount);
     }
 
     fn transfer_from(env: Env, spender: Address, from: Address, to: Address, amount: i128) {
         spender.require_auth();
 
         assert_nonnegative_amount(amount);
-        assert_account_not_frozen(&env, &from);
-
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL);
-
         spend_allowance(&env, from.clone(), spender, amount);
-        decrease_balance(&env, from.clone(), amount);
-        increase_balance(&env, to.clone(), amount);
 
-        TokenUtils::new(&env).events().transfer(from, to, amount);
+        move_balance(&env, Movement::Transfer { from, to }, amount);
     }
 
     fn burn(env: Env, from: Address, amount: i128) {
         from.require_auth();
 
-        assert_nonnegative_amount(amount);
-        assert_account_not_frozen(&env, &from);
-
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL);
-
-        decrease_balance(&env, from.clone(), amount);
-
-        TokenUtils::new(&env).events().burn(from, amount);
+        move_balance(&env, Movement::Burn { from }, amount);
     }
 
     fn burn_from(env: Env, spender: Address, from: Address, amount: i128) {
         spender.require_auth();
 
         assert_nonnegative_amount(amount);
-        assert_account_not_frozen(&env, &from);
-
-        env.storage()
-            .instance()
-            .extend_ttl(INSTANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL);
-
         spend_allowance(&env, from.clone(), spender, amount);
-        decrease_balance(&env, from.clone(), amount);
 
-        TokenUtils::new(&env).events().burn(from, amount);
+        move_balance(&env, Movement::Burn { from }, amount);
     }
 
     fn decimals(env: Env) -> u32 {
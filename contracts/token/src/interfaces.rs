@@ -0,0 +1,25 @@
+use soroban_sdk::{contractclient, Address, Bytes, Env};
+
+// Implemented by contracts that want to be notified by `transfer_and_call`.
+// Returning `false` rejects the transfer.
+#[contractclient(name = "TokenReceiverClient")]
+pub trait TokenReceiver {
+    fn on_token_received(
+        env: Env,
+        token: Address,
+        from: Address,
+        amount: i128,
+        data: Bytes,
+    ) -> bool;
+}
+
+// Implemented by compliance contracts registered through `set_transfer_rules`. It is
+// consulted before every balance movement; `from` is `None` for mints and `to` is
+// `None` for burns. Returning `false` aborts the movement.
+//
+// Like the other callbacks, the rules contract can't call back into this token, so it
+// has to keep whatever state its rules depend on.
+#[contractclient(name = "TransferRulesClient")]
+pub trait TransferRules {
+    fn can_transfer(env: Env, from: Option<Address>, to: Option<Address>, amount: i128) -> bool;
+}
@@ -2,7 +2,11 @@
 
 mod constants;
 mod contract;
+pub mod errors;
+pub mod interfaces;
 mod storage;
 mod utils;
 
 mod test;
+
+pub use contract::{TokenContract, TokenContractClient};
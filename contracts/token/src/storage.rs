@@ -1,4 +1,4 @@
-use soroban_sdk::{contracttype, Address};
+use soroban_sdk::{contracttype, Address, BytesN, Vec, I256};
 
 #[derive(Clone)]
 #[contracttype]
@@ -13,6 +13,138 @@ pub struct AllowanceData {
     pub expiration_ledgers: u32,
 }
 
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct AdminCouncil {
+    pub signers: Vec<Address>,
+    pub threshold: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub enum AdminAction {
+    Mint(Address, i128),
+    SetAdmin(Address),
+    FreezeAccount(Address),
+    FreezeAccountUntil(Address, u32),
+    UnfreezeAccount(Address),
+    SetCouncil(Vec<Address>, u32),
+    Rebase(i128),
+    SetRewardToken(Address),
+    SetRewardExcluded(Address, bool),
+    SetStakingRewardRate(i128),
+    SetTtlPolicy(TtlPolicy),
+    SetTransferRules(Option<Address>),
+    SetHolderLimits(HolderLimits),
+    FreezeAmount(Address, i128),
+    UnfreezeAmount(Address, i128),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Proposal {
+    pub proposer: Address,
+    pub action: AdminAction,
+    pub approvals: Vec<Address>,
+    pub executed: bool,
+    pub council_epoch: u32,
+    pub expiration_ledger: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Subscription {
+    pub payer: Address,
+    pub merchant: Address,
+    pub amount_per_period: i128,
+    pub period_ledgers: u32,
+    pub max_periods: u32,
+    pub start_ledger: u32,
+    pub collected_periods: u32,
+    pub next_period: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Stream {
+    pub sender: Address,
+    pub recipient: Address,
+    pub deposit: i128,
+    pub start_ledger: u32,
+    pub stop_ledger: u32,
+    pub withdrawn: i128,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct Htlc {
+    pub from: Address,
+    pub to: Address,
+    pub amount: i128,
+    pub hashlock: BytesN<32>,
+    pub timeout_ledger: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct RewardAccount {
+    pub correction: I256,
+    pub withdrawn: i128,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct StakingPool {
+    pub total_staked: i128,
+    pub reward_rate: i128,
+    pub reward_reserve: i128,
+    pub reward_per_token: I256,
+    pub last_update_ledger: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct StakeInfo {
+    pub amount: i128,
+    pub reward_per_token_paid: I256,
+    pub rewards: i128,
+    pub unbonding_amount: i128,
+    pub unbonding_until_ledger: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct TtlPolicy {
+    pub instance_ttl_threshold: u32,
+    pub instance_ttl_full: u32,
+    pub balance_ttl_threshold: u32,
+    pub balance_ttl_full: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct HolderLimits {
+    pub max_holders: Option<u32>,
+    pub max_balance_per_account: Option<i128>,
+}
+
+// The exact live-until ledger of an entry can't be read from inside a contract,
+// so this reports whether the entry exists along with the extension policy
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[contracttype]
+pub struct TtlInfo {
+    pub has_balance: bool,
+    pub policy: TtlPolicy,
+    pub max_ttl: u32,
+}
+
+#[derive(Clone)]
+#[contracttype]
+pub enum AddressIndex {
+    Holders,
+    Frozen,
+}
+
 #[derive(Clone)]
 #[contracttype]
 pub enum DataKey {
@@ -20,6 +152,33 @@ pub enum DataKey {
     Balance(Address),
     Nonce(Address),
     State(Address),
-    Admin,
+    Council,
+    CouncilEpoch,
     Frozen(Address),
+    FrozenAmount(Address),
+    ProposalCount,
+    Proposal(u32),
+    Spenders(Address),
+    SubscriptionCount,
+    Subscription(u32),
+    StreamCount,
+    Stream(u32),
+    HtlcCount,
+    Htlc(u32),
+    TotalSupply,
+    Underlying,
+    RebaseIndex,
+    RewardToken,
+    RewardPerShare,
+    RewardEligibleShares,
+    RewardExcluded(Address),
+    Rewards(Address),
+    StakingPool,
+    Stake(Address),
+    TtlPolicy,
+    TransferRules,
+    IndexLen(AddressIndex),
+    IndexChunk(AddressIndex, u32),
+    IndexPosition(AddressIndex, Address),
+    HolderLimits,
 }
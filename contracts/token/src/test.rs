@@ -2,13 +2,69 @@
 
 extern crate std; // For "vec!"
 
-use crate::contract::{TokenContract, TokenContractClient};
+use crate::{
+    constants::{
+        BALANCE_TTL_FULL, DAY_IN_LEDGERS, FREEZE_INDEFINITELY, INSTANCE_TTL_FULL, MAX_PAGE_SIZE,
+    },
+    contract::{TokenContract, TokenContractClient},
+    errors::TokenError,
+    storage::{
+        AddressIndex, AdminAction, AllowanceData, AllowanceDataKey, DataKey, HolderLimits,
+        TtlPolicy,
+    },
+    utils::balance::{decrease_balance, increase_balance, read_balance},
+};
 use soroban_sdk::{
-    symbol_short,
-    testutils::{Address as _, AuthorizedFunction, AuthorizedInvocation},
-    Address, Env, IntoVal, String, Symbol,
+    contract, contractimpl, symbol_short,
+    testutils::{
+        storage::{Instance as _, Persistent as _},
+        Address as _, AuthorizedFunction, AuthorizedInvocation, EnvTestConfig, Events, Ledger,
+    },
+    token::{StellarAssetClient, TokenClient},
+    vec, Address, Bytes, Env, IntoVal, String, Symbol, Vec,
 };
 
+#[contract]
+struct TokenReceiver;
+
+#[contractimpl]
+impl TokenReceiver {
+    pub fn on_token_received(
+        env: Env,
+        token: Address,
+        from: Address,
+        amount: i128,
+        data: Bytes,
+    ) -> bool {
+        env.storage()
+            .instance()
+            .set(&symbol_short!("received"), &(token, from, amount));
+        data != Bytes::from_slice(&env, b"reject")
+    }
+}
+
+#[contract]
+struct TransferRules;
+
+#[contractimpl]
+impl TransferRules {
+    pub fn set_blocked(env: Env, account: Address) {
+        env.storage()
+            .instance()
+            .set(&symbol_short!("blocked"), &account);
+    }
+
+    pub fn can_transfer(
+        env: Env,
+        _from: Option<Address>,
+        to: Option<Address>,
+        _amount: i128,
+    ) -> bool {
+        let blocked: Option<Address> = env.storage().instance().get(&symbol_short!("blocked"));
+        to.is_none() || to != blocked
+    }
+}
+
 fn create_token<'a>(
     env: &Env,
     admin: &Address,
@@ -22,6 +78,19 @@ fn create_token<'a>(
     client
 }
 
+fn get_allowance_data(
+    env: &Env,
+    token: &TokenContractClient,
+    from: &Address,
+    spender: &Address,
+) -> Option<AllowanceData> {
+    let key = DataKey::Allowance(AllowanceDataKey {
+        owner: from.clone(),
+        spender: spender.clone(),
+    });
+    env.as_contract(&token.address, || env.storage().temporary().get(&key))
+}
+
 #[test]
 fn test_1() {
     let env = Env::default();
@@ -378,5 +447,1820 @@ fn test_zero_allowance() {
 
     // Using From's approval, Spender transfers 0 tokens from From to Spender
     token.transfer_from(&spender, &from, &spender, &0);
-    assert!(token.test_get_allowance(&from, &spender).is_none());
+    assert!(get_allowance_data(&env, &token, &from, &spender).is_none());
+}
+
+#[test]
+fn test_council() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let signer1 = Address::generate(&env);
+    let signer2 = Address::generate(&env);
+    let signer3 = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+
+    // Admin hands over control to a 2-of-3 council
+    let signers = vec![&env, signer1.clone(), signer2.clone(), signer3.clone()];
+    token.set_council(&signers, &2);
+    assert_eq!(token.council().signers, signers);
+    assert_eq!(token.council().threshold, 2);
+
+    // Signer 1 proposes minting 1000 tokens to User, nothing happens yet
+    let id = token.propose(&signer1, &AdminAction::Mint(user.clone(), 1000));
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            signer1.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    symbol_short!("propose"),
+                    (&signer1, AdminAction::Mint(user.clone(), 1000)).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    assert_eq!(token.balance(&user), 0);
+    assert!(!token.proposal(&id).executed);
+
+    // Signer 2 approves, threshold is reached and the mint is executed
+    token.approve_proposal(&signer2, &id);
+    assert_eq!(token.balance(&user), 1000);
+    assert!(token.proposal(&id).executed);
+
+    // Changing the signer set requires threshold approval as well
+    let id = token.propose(
+        &signer1,
+        &AdminAction::SetCouncil(vec![&env, signer1.clone()], 1),
+    );
+    assert_eq!(token.council().signers.len(), 3);
+    token.approve_proposal(&signer3, &id);
+    assert_eq!(token.council().signers, vec![&env, signer1.clone()]);
+    assert_eq!(token.council().threshold, 1);
+
+    // With a single signer left, direct admin entry points work again
+    token.freeze_account(&user);
+    token.unfreeze_account(&user);
+    token.mint(&user, &1);
+    assert_eq!(token.balance(&user), 1001);
+}
+
+#[test]
+#[should_panic(expected = "Admin operations require council approval")]
+fn test_council_direct_admin_call() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let signer = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.set_council(&vec![&env, admin.clone(), signer.clone()], &2);
+
+    // This should panic
+    token.mint(&user, &1000);
+}
+
+#[test]
+#[should_panic(expected = "Not a council signer")]
+fn test_council_approve_by_non_signer() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let signer = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.set_council(&vec![&env, admin.clone(), signer.clone()], &2);
+
+    let id = token.propose(&admin, &AdminAction::Mint(user.clone(), 1000));
+
+    // This should panic
+    token.approve_proposal(&user, &id);
+}
+
+#[test]
+#[should_panic(expected = "Proposal already approved by signer")]
+fn test_council_double_approval() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let signer1 = Address::generate(&env);
+    let signer2 = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.set_council(
+        &vec![&env, signer1.clone(), signer2.clone(), admin.clone()],
+        &3,
+    );
+
+    let id = token.propose(&signer1, &AdminAction::FreezeAccount(user.clone()));
+    token.approve_proposal(&signer2, &id);
+
+    // This should panic
+    token.approve_proposal(&signer2, &id);
+}
+
+#[test]
+#[should_panic(expected = "Proposal was made under a previous council")]
+fn test_council_stale_proposal() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let signer1 = Address::generate(&env);
+    let signer2 = Address::generate(&env);
+    let signer3 = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.set_council(
+        &vec![&env, signer1.clone(), signer2.clone(), signer3.clone()],
+        &2,
+    );
+
+    let id = token.propose(&signer1, &AdminAction::Mint(user.clone(), 1000));
+
+    // The council is rotated before the mint is approved
+    let rotation = token.propose(
+        &signer1,
+        &AdminAction::SetCouncil(vec![&env, signer1.clone(), signer2.clone()], 2),
+    );
+    token.approve_proposal(&signer3, &rotation);
+
+    // This should panic
+    token.approve_proposal(&signer2, &id);
+}
+
+#[test]
+#[should_panic(expected = "Proposal expired")]
+fn test_council_expired_proposal() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let signer = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.set_council(&vec![&env, admin.clone(), signer.clone()], &2);
+
+    let id = token.propose(&admin, &AdminAction::Mint(user.clone(), 1000));
+
+    // Keep the instance alive while the proposal lifetime runs out
+    env.ledger()
+        .with_mut(|li| li.sequence_number += 4 * DAY_IN_LEDGERS);
+    token.balance(&user);
+    env.ledger()
+        .with_mut(|li| li.sequence_number += 4 * DAY_IN_LEDGERS);
+
+    // This should panic
+    token.approve_proposal(&signer, &id);
+}
+
+#[test]
+fn test_increase_decrease_allowance() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+
+    // User 1 increases User 2's allowance twice
+    token.increase_allowance(&user1, &user2, &300, &200);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            user1.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    Symbol::new(&env, "increase_allowance"),
+                    (&user1, &user2, 300_i128, 200_u32).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    token.increase_allowance(&user1, &user2, &200, &200);
+    assert_eq!(token.allowance(&user1, &user2), 500);
+
+    // User 1 decreases User 2's allowance
+    token.decrease_allowance(&user1, &user2, &100);
+    assert_eq!(token.allowance(&user1, &user2), 400);
+
+    // Compare-and-set succeeds only against the current allowance
+    token.approve_if(&user1, &user2, &400, &50, &200);
+    assert_eq!(token.allowance(&user1, &user2), 50);
+    assert!(token
+        .try_approve_if(&user1, &user2, &400, &1000, &200)
+        .is_err());
+    assert_eq!(token.allowance(&user1, &user2), 50);
+}
+
+#[test]
+#[should_panic(expected = "Allowance underflow")]
+fn test_decrease_allowance_underflow() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+
+    token.approve(&user1, &user2, &100, &200);
+
+    // This should panic
+    token.decrease_allowance(&user1, &user2, &101);
+}
+
+#[test]
+#[should_panic(expected = "Allowance overflow")]
+fn test_increase_allowance_overflow() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+
+    token.approve(&user1, &user2, &i128::MAX, &200);
+
+    // This should panic
+    token.increase_allowance(&user1, &user2, &1, &200);
+}
+
+#[test]
+fn test_allowances_and_revoke_all() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let owner = Address::generate(&env);
+    let spender1 = Address::generate(&env);
+    let spender2 = Address::generate(&env);
+    let spender3 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&owner, &1000);
+
+    // Owner approves three spenders, then removes one approval
+    token.approve(&owner, &spender1, &100, &200);
+    token.approve(&owner, &spender2, &200, &300);
+    token.approve(&owner, &spender3, &300, &400);
+    token.approve(&owner, &spender3, &0, &400);
+    assert_eq!(
+        token.allowances(&owner),
+        vec![
+            &env,
+            (spender1.clone(), 100_i128, 200_u32),
+            (spender2.clone(), 200_i128, 300_u32),
+        ]
+    );
+
+    // Fully spent allowances drop out of the index
+    token.transfer_from(&spender1, &owner, &spender1, &60);
+    token.transfer_from(&spender1, &owner, &spender1, &40);
+    assert_eq!(
+        token.allowances(&owner),
+        vec![&env, (spender2.clone(), 200_i128, 300_u32)]
+    );
+
+    // Allowances that expire on their own are pruned from the index once it is read
+    let spender4 = Address::generate(&env);
+    token.approve(&owner, &spender4, &100, &50);
+    env.ledger().set_sequence_number(100);
+    assert_eq!(
+        token.allowances(&owner),
+        vec![&env, (spender2.clone(), 200_i128, 300_u32)]
+    );
+    let spenders = || {
+        env.as_contract(&token.address, || {
+            env.storage()
+                .persistent()
+                .get::<_, Vec<Address>>(&DataKey::Spenders(owner.clone()))
+        })
+    };
+    assert_eq!(spenders(), Some(vec![&env, spender2.clone()]));
+
+    // Owner revokes everything
+    token.approve(&owner, &spender3, &50, &400);
+    token.revoke_all(&owner);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            owner.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    Symbol::new(&env, "revoke_all"),
+                    (&owner,).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    assert_eq!(token.allowances(&owner).len(), 0);
+    assert_eq!(token.allowance(&owner, &spender2), 0);
+    assert_eq!(token.allowance(&owner, &spender3), 0);
+    assert_eq!(spenders(), None);
+}
+
+#[test]
+fn test_unlimited_allowance() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+    let spender = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+    token.mint(&user2, &1000);
+    // The spender is already a holder, so both transfers below write the same balance entries
+    token.mint(&spender, &1);
+
+    // User 1 approves a limited allowance, User 2 an unlimited one
+    token.approve(&user1, &spender, &1000, &200);
+    token.approve(&user2, &spender, &i128::MAX, &200);
+
+    token.transfer_from(&spender, &user1, &spender, &100);
+    let limited_writes = env.cost_estimate().resources().write_entries;
+    assert_eq!(token.allowance(&user1, &spender), 900);
+
+    // Spending an unlimited allowance doesn't rewrite the allowance entry
+    token.transfer_from(&spender, &user2, &spender, &100);
+    let unlimited_writes = env.cost_estimate().resources().write_entries;
+    assert_eq!(token.allowance(&user2, &spender), i128::MAX);
+    assert_eq!(unlimited_writes, limited_writes - 1);
+    assert_eq!(
+        get_allowance_data(&env, &token, &user2, &spender)
+            .unwrap()
+            .amount,
+        i128::MAX
+    );
+
+    // Unlimited allowances still expire
+    env.ledger().set_sequence_number(201);
+    assert_eq!(token.allowance(&user2, &spender), 0);
+    assert!(token
+        .try_transfer_from(&spender, &user2, &spender, &100)
+        .is_err());
+}
+
+#[test]
+fn test_max_allowance_expiration() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+
+    let max_expiration = token.max_allowance_expiration();
+    assert_eq!(
+        max_expiration,
+        env.ledger().sequence() + env.ledger().get().max_entry_ttl - 1
+    );
+
+    // An allowance with the maximum expiration stays readable until it expires
+    token.approve(&user1, &user2, &500, &max_expiration);
+
+    // Keep the contract instance alive for the whole lifetime of the allowance
+    env.as_contract(&token.address, || {
+        let max_ttl = env.storage().max_ttl();
+        env.storage().instance().extend_ttl(max_ttl, max_ttl);
+    });
+    env.ledger().set_sequence_number(max_expiration);
+    assert_eq!(token.allowance(&user1, &user2), 500);
+}
+
+#[test]
+#[should_panic(
+    expected = "expiration_ledgers can't be greater than the maximum allowance expiration"
+)]
+fn test_allowance_expiration_over_max() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+
+    // This should panic
+    token.approve(
+        &user1,
+        &user2,
+        &500,
+        &(token.max_allowance_expiration() + 1),
+    );
+}
+
+#[test]
+fn test_subscription() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let payer = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&payer, &1000);
+
+    // Payer subscribes to 100 tokens per 10 ledgers for 3 periods
+    let id = token.subscribe(&payer, &merchant, &100, &10, &3);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            payer.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    symbol_short!("subscribe"),
+                    (&payer, &merchant, 100_i128, 10_u32, 3_u32).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+
+    // Merchant collects the first period, but can't collect twice in it
+    token.collect(&id);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            merchant.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    symbol_short!("collect"),
+                    (id,).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    assert_eq!(token.balance(&payer), 900);
+    assert_eq!(token.balance(&merchant), 100);
+    assert!(token.try_collect(&id).is_err());
+
+    // Missed periods are not accumulated
+    env.ledger().with_mut(|li| li.sequence_number += 25);
+    token.collect(&id);
+    assert!(token.try_collect(&id).is_err());
+    assert_eq!(token.balance(&merchant), 200);
+
+    // The subscription ends after the last period
+    env.ledger().with_mut(|li| li.sequence_number += 10);
+    token.collect(&id);
+    assert_eq!(token.balance(&payer), 700);
+    assert_eq!(token.balance(&merchant), 300);
+    assert!(token.try_subscription(&id).is_err());
+}
+
+#[test]
+#[should_panic(expected = "Subscription not found")]
+fn test_subscription_cancelled() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let payer = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&payer, &1000);
+
+    let id = token.subscribe(&payer, &merchant, &100, &10, &3);
+    token.cancel_subscription(&id);
+
+    // This should panic
+    token.collect(&id);
+}
+
+#[test]
+#[should_panic(expected = "Account is frozen")]
+fn test_subscription_payer_frozen() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let payer = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&payer, &1000);
+
+    let id = token.subscribe(&payer, &merchant, &100, &10, &3);
+    token.freeze_account(&payer);
+
+    // This should panic
+    token.collect(&id);
+}
+
+#[test]
+fn test_stream() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&sender, &1000);
+
+    // Sender streams 1000 tokens to Recipient between ledgers 10 and 110
+    let id = token.create_stream(&sender, &recipient, &1000, &10, &110);
+    assert_eq!(token.balance(&sender), 0);
+    assert_eq!(token.balance(&token.address), 1000);
+    assert_eq!(token.stream_balance(&id, &recipient), 0);
+    assert_eq!(token.stream_balance(&id, &sender), 1000);
+
+    // A quarter of the way through, a quarter is unlocked
+    env.ledger().set_sequence_number(35);
+    assert_eq!(token.stream_balance(&id, &recipient), 250);
+    assert_eq!(token.stream_balance(&id, &sender), 750);
+
+    token.withdraw_from_stream(&id, &200);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            recipient.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    Symbol::new(&env, "withdraw_from_stream"),
+                    (id, 200_i128).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    assert_eq!(token.balance(&recipient), 200);
+    assert_eq!(token.stream_balance(&id, &recipient), 50);
+    assert!(token.try_withdraw_from_stream(&id, &51).is_err());
+
+    // Cancelling halfway splits the remaining deposit
+    env.ledger().set_sequence_number(60);
+    token.cancel_stream(&id);
+    assert_eq!(token.balance(&recipient), 500);
+    assert_eq!(token.balance(&sender), 500);
+    assert_eq!(token.balance(&token.address), 0);
+    assert!(token.try_stream(&id).is_err());
+}
+
+#[test]
+fn test_stream_rounding() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let sender = Address::generate(&env);
+    let recipient = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&sender, &i128::MAX);
+
+    // Large deposits don't overflow and odd amounts round down for the recipient
+    let id = token.create_stream(&sender, &recipient, &i128::MAX, &0, &3);
+    env.ledger().set_sequence_number(1);
+    assert_eq!(token.stream_balance(&id, &recipient), i128::MAX / 3);
+    assert_eq!(
+        token.stream_balance(&id, &sender),
+        i128::MAX - i128::MAX / 3
+    );
+
+    // Everything is withdrawable after the stop ledger
+    env.ledger().set_sequence_number(3);
+    token.withdraw_from_stream(&id, &i128::MAX);
+    assert_eq!(token.balance(&recipient), i128::MAX);
+    assert!(token.try_stream(&id).is_err());
+}
+
+#[test]
+fn test_htlc_redeem() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+
+    let preimage = Bytes::from_slice(&env, b"secret");
+    let hashlock = env.crypto().sha256(&preimage).to_bytes();
+
+    // User 1 locks 400 tokens for User 2 until ledger 100
+    let id = token.lock_htlc(&user1, &user2, &400, &hashlock, &100);
+    assert_eq!(token.balance(&user1), 600);
+    assert_eq!(token.balance(&token.address), 400);
+
+    // A wrong preimage is rejected, the right one releases the funds
+    assert!(token
+        .try_redeem_htlc(&id, &Bytes::from_slice(&env, b"guess"))
+        .is_err());
+    token.redeem_htlc(&id, &preimage);
+    assert_eq!(token.balance(&user2), 400);
+    assert_eq!(token.balance(&token.address), 0);
+    assert!(token.try_htlc(&id).is_err());
+}
+
+#[test]
+fn test_htlc_refund() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+
+    let preimage = Bytes::from_slice(&env, b"secret");
+    let hashlock = env.crypto().sha256(&preimage).to_bytes();
+
+    let id = token.lock_htlc(&user1, &user2, &400, &hashlock, &100);
+
+    // Refunds are only possible after the timeout, and redeems only before it
+    assert!(token.try_refund_htlc(&id).is_err());
+    env.ledger().set_sequence_number(100);
+    assert!(token.try_redeem_htlc(&id, &preimage).is_err());
+    token.refund_htlc(&id);
+    assert_eq!(token.balance(&user1), 1000);
+    assert_eq!(token.balance(&user2), 0);
+}
+
+#[test]
+#[should_panic(expected = "Account is frozen")]
+fn test_htlc_redeem_frozen() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+
+    let preimage = Bytes::from_slice(&env, b"secret");
+    let hashlock = env.crypto().sha256(&preimage).to_bytes();
+
+    let id = token.lock_htlc(&user1, &user2, &400, &hashlock, &100);
+    token.freeze_account(&user2);
+
+    // This should panic
+    token.redeem_htlc(&id, &preimage);
+}
+
+#[test]
+fn test_escrow_frozen_sender() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+    token.freeze_account(&user1);
+
+    // Funding an escrow is a balance movement, so the shared pipeline rejects frozen senders
+    let hashlock = env.crypto().sha256(&Bytes::from_slice(&env, b"secret"));
+    assert!(token
+        .try_lock_htlc(&user1, &user2, &100, &hashlock.to_bytes(), &100)
+        .is_err());
+    assert!(token
+        .try_create_stream(&user1, &user2, &100, &10, &110)
+        .is_err());
+    assert!(token.try_stake(&user1, &100).is_err());
+    assert!(token.try_fund_staking_rewards(&user1, &100).is_err());
+    assert!(token.try_distribute(&user1, &100).is_err());
+    assert_eq!(token.balance(&user1), 1000);
+}
+
+#[test]
+fn test_transfer_with_memo() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+
+    // User 1 transfers 300 tokens to User 2 with a memo
+    token.transfer_with_memo(&user1, &user2, &300, &42);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            user1.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    Symbol::new(&env, "transfer_with_memo"),
+                    (&user1, &user2, 300_i128, 42_u64).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+
+    // Both the standard transfer event and the memo event are emitted
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            (
+                token.address.clone(),
+                (symbol_short!("transfer"), &user1, &user2).into_val(&env),
+                300_i128.into_val(&env),
+            ),
+            (
+                token.address.clone(),
+                (String::from_str(&env, "transfer_with_memo"), &user1, &user2).into_val(&env),
+                (300_i128, 42_u64).into_val(&env),
+            ),
+        ]
+    );
+    assert_eq!(token.balance(&user1), 700);
+    assert_eq!(token.balance(&user2), 300);
+}
+
+#[test]
+fn test_transfer_and_call() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user = Address::generate(&env);
+    let receiver = env.register(TokenReceiver, ());
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user, &1000);
+
+    // User deposits 300 tokens into Receiver in a single call
+    token.transfer_and_call(&user, &receiver, &300, &Bytes::new(&env));
+    assert_eq!(token.balance(&user), 700);
+    assert_eq!(token.balance(&receiver), 300);
+    let received = env.as_contract(&receiver, || {
+        env.storage()
+            .instance()
+            .get::<_, (Address, Address, i128)>(&symbol_short!("received"))
+    });
+    assert_eq!(received, Some((token.address.clone(), user.clone(), 300)));
+
+    // A rejection from Receiver reverts the whole transfer
+    assert!(token
+        .try_transfer_and_call(&user, &receiver, &300, &Bytes::from_slice(&env, b"reject"))
+        .is_err());
+    assert_eq!(token.balance(&user), 700);
+    assert_eq!(token.balance(&receiver), 300);
+
+    // Accounts that aren't contracts can't receive with transfer_and_call
+    assert!(token
+        .try_transfer_and_call(&user, &admin, &300, &Bytes::new(&env))
+        .is_err());
+    assert_eq!(token.balance(&user), 700);
+}
+
+#[test]
+fn test_total_supply() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+
+    token.mint(&user1, &1000);
+    token.mint(&user2, &500);
+    assert_eq!(token.total_supply(), 1500);
+
+    token.transfer(&user1, &user2, &300);
+    token.burn(&user2, &200);
+    token.approve(&user1, &user2, &100, &200);
+    token.burn_from(&user2, &user1, &100);
+    assert_eq!(token.total_supply(), 1200);
+    assert_eq!(
+        token.total_supply(),
+        token.balance(&user1) + token.balance(&user2)
+    );
+}
+
+#[test]
+fn test_wrapped_token() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let usdc = env.register_stellar_asset_contract_v2(admin.clone());
+    StellarAssetClient::new(&env, &usdc.address()).mint(&user, &1000);
+    let usdc = TokenClient::new(&env, &usdc.address());
+
+    let contract_id = env.register(TokenContract, ());
+    let token = TokenContractClient::new(&env, &contract_id);
+    token.initialize_wrapped(
+        &admin,
+        &usdc.address,
+        &"Wrapped USDC".into_val(&env),
+        &"wUSDC".into_val(&env),
+    );
+    assert_eq!(token.underlying(), Some(usdc.address.clone()));
+    assert_eq!(token.decimals(), usdc.decimals());
+
+    // User wraps 600 USDC
+    token.deposit(&user, &600);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            user.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    symbol_short!("deposit"),
+                    (&user, 600_i128).into_val(&env),
+                )),
+                sub_invocations: std::vec![AuthorizedInvocation {
+                    function: AuthorizedFunction::Contract((
+                        usdc.address.clone(),
+                        symbol_short!("transfer"),
+                        (&user, &token.address, 600_i128).into_val(&env),
+                    )),
+                    sub_invocations: std::vec![]
+                }]
+            }
+        )]
+    );
+    assert_eq!(token.balance(&user), 600);
+    assert_eq!(usdc.balance(&user), 400);
+    assert_eq!(usdc.balance(&token.address), 600);
+
+    // User unwraps 200 USDC
+    token.withdraw(&user, &200);
+    assert_eq!(token.balance(&user), 400);
+    assert_eq!(usdc.balance(&user), 600);
+    assert_eq!(token.total_supply(), usdc.balance(&token.address));
+
+    // Minting unbacked tokens breaks the invariant
+    assert!(token.try_mint(&admin, &1).is_err());
+}
+
+#[test]
+fn test_rebase() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+    token.mint(&user2, &3000);
+
+    // Before any rebase, shares equal balances
+    assert_eq!(token.rebase_index(), 1_000_000_000);
+    assert_eq!(token.shares_of(&user1), 1000);
+
+    // A 10% rebase grows every balance without touching the accounts
+    token.rebase(&1_100_000_000);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    symbol_short!("rebase"),
+                    (1_100_000_000_i128,).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    assert_eq!(token.balance(&user1), 1100);
+    assert_eq!(token.balance(&user2), 3300);
+    assert_eq!(token.total_supply(), 4400);
+    assert_eq!(token.shares_of(&user1), 1000);
+
+    // Transfers move the same shares out of the sender and into the recipient
+    token.transfer(&user1, &user2, &110);
+    assert_eq!(token.shares_of(&user1), 900);
+    assert_eq!(token.shares_of(&user2), 3100);
+    assert_eq!(token.balance(&user1), 990);
+    assert_eq!(token.balance(&user2), 3410);
+    assert_eq!(
+        token.balance(&user1) + token.balance(&user2),
+        token.total_supply()
+    );
+
+    // Shares are rounded against the sender, the recipient is credited the full amount
+    token.transfer(&user1, &user2, &100);
+    assert_eq!(token.shares_of(&user1), 809);
+    assert_eq!(token.shares_of(&user2), 3191);
+    assert_eq!(token.balance(&user2), 3510);
+    assert_eq!(token.total_supply(), 4400);
+
+    // Balances can shrink too
+    token.rebase(&1_000_000_000);
+    assert_eq!(token.balance(&user1), 809);
+    assert!(token.try_transfer(&user1, &user2, &810).is_err());
+}
+
+#[test]
+fn test_rebase_with_escrows() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+
+    let preimage = Bytes::from_slice(&env, b"secret");
+    let hashlock = env.crypto().sha256(&preimage).to_bytes();
+    let htlc_id = token.lock_htlc(&user1, &user2, &90, &hashlock, &100);
+    let stream_id = token.create_stream(&user1, &user2, &90, &10, &110);
+    token.stake(&user1, &90);
+    assert_eq!(token.balance(&token.address), 270);
+
+    // Escrowed funds don't rebase, so every escrow stays fully backed
+    token.rebase(&900_000_000);
+    assert_eq!(token.balance(&user1), 657);
+    assert_eq!(token.balance(&token.address), 270);
+    assert_eq!(token.total_supply(), 927);
+
+    token.redeem_htlc(&htlc_id, &preimage);
+    assert_eq!(token.balance(&user2), 90);
+
+    token.cancel_stream(&stream_id);
+    assert_eq!(token.balance(&user1), 747);
+
+    // Wait out the unbonding period in two steps to keep the contract instance alive
+    token.unstake(&user1, &90);
+    env.ledger()
+        .with_mut(|li| li.sequence_number += 4 * DAY_IN_LEDGERS);
+    token.extend_instance_ttl();
+    env.ledger()
+        .with_mut(|li| li.sequence_number += 3 * DAY_IN_LEDGERS);
+    token.withdraw_unstaked(&user1);
+
+    assert_eq!(token.balance(&user1), 837);
+    assert_eq!(token.balance(&token.address), 0);
+    assert_eq!(
+        token.balance(&user1) + token.balance(&user2),
+        token.total_supply()
+    );
+}
+
+#[test]
+fn test_rebase_with_stray_transfer() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user, &1000);
+
+    // Tokens sent straight to the contract address don't block rebases
+    token.transfer(&user, &token.address, &1);
+    token.rebase(&1_100_000_000);
+    assert_eq!(token.balance(&user), 1098);
+    assert_eq!(token.balance(&token.address), 1);
+    assert_eq!(token.total_supply(), 1099);
+}
+
+#[test]
+fn test_rebase_with_reward_dust() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+    let user3 = Address::generate(&env);
+    let distributor = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &100);
+    token.mint(&user2, &100);
+    token.mint(&user3, &100);
+    token.mint(&distributor, &10);
+    token.set_reward_excluded(&distributor, &true);
+
+    // 10 can't be split evenly across three holders, so 1 stays on the contract
+    token.distribute(&distributor, &10);
+    assert_eq!(token.claim_rewards(&user1), 3);
+    assert_eq!(token.claim_rewards(&user2), 3);
+    assert_eq!(token.claim_rewards(&user3), 3);
+    assert_eq!(token.balance(&token.address), 1);
+
+    // The leftover dust doesn't block rebases
+    token.rebase(&1_100_000_000);
+    assert_eq!(token.balance(&user1), 113);
+    assert_eq!(token.balance(&token.address), 1);
+}
+
+#[test]
+#[should_panic(expected = "Rebase is not supported for wrapped tokens")]
+fn test_rebase_wrapped_token() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let usdc = env.register_stellar_asset_contract_v2(admin.clone());
+    StellarAssetClient::new(&env, &usdc.address()).mint(&user, &1000);
+
+    let contract_id = env.register(TokenContract, ());
+    let token = TokenContractClient::new(&env, &contract_id);
+    token.initialize_wrapped(
+        &admin,
+        &usdc.address(),
+        &"Wrapped USDC".into_val(&env),
+        &"wUSDC".into_val(&env),
+    );
+    token.deposit(&user, &600);
+
+    // This should panic
+    token.rebase(&1_100_000_000);
+}
+
+#[test]
+#[should_panic(expected = "Rebase index change exceeds the limit")]
+fn test_rebase_over_limit() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+
+    // This should panic
+    token.rebase(&1_100_000_001);
+}
+
+#[test]
+fn test_rewards() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+    let pool = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+    token.mint(&user2, &3000);
+    token.mint(&pool, &4000);
+    token.mint(&admin, &2000);
+
+    // The DEX pool and the distributor don't earn rewards
+    token.set_reward_excluded(&pool, &true);
+    token.set_reward_excluded(&admin, &true);
+    env.as_contract(&token.address, || {
+        assert!(env
+            .storage()
+            .persistent()
+            .has(&DataKey::RewardExcluded(pool.clone())));
+    });
+
+    // Admin distributes 400 tokens to User 1 and User 2 pro-rata
+    token.distribute(&admin, &400);
+    assert_eq!(token.withdrawable_rewards(&user1), 100);
+    assert_eq!(token.withdrawable_rewards(&user2), 300);
+    assert_eq!(token.withdrawable_rewards(&pool), 0);
+    assert_eq!(token.balance(&token.address), 400);
+
+    // Rewards stay with the holder at distribution time
+    token.transfer(&user2, &user1, &2000);
+    assert_eq!(token.withdrawable_rewards(&user1), 100);
+    assert_eq!(token.withdrawable_rewards(&user2), 300);
+
+    token.distribute(&admin, &400);
+    assert_eq!(token.withdrawable_rewards(&user1), 400);
+    assert_eq!(token.withdrawable_rewards(&user2), 400);
+
+    // User 1 claims
+    assert_eq!(token.claim_rewards(&user1), 400);
+    assert_eq!(token.balance(&user1), 3400);
+    assert_eq!(token.withdrawable_rewards(&user1), 0);
+    assert_eq!(token.claim_rewards(&user1), 0);
+
+    // Including an account starts earning only from then on
+    token.set_reward_excluded(&pool, &false);
+    assert_eq!(token.withdrawable_rewards(&pool), 0);
+}
+
+#[test]
+fn test_rewards_in_other_token() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let usdc = env.register_stellar_asset_contract_v2(admin.clone());
+    StellarAssetClient::new(&env, &usdc.address()).mint(&admin, &1000);
+    let usdc = TokenClient::new(&env, &usdc.address());
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.set_reward_token(&usdc.address);
+    token.mint(&user1, &100);
+    token.mint(&user2, &300);
+
+    token.distribute(&admin, &1000);
+    assert_eq!(usdc.balance(&token.address), 1000);
+
+    assert_eq!(token.claim_rewards(&user2), 750);
+    assert_eq!(usdc.balance(&user2), 750);
+    assert_eq!(token.withdrawable_rewards(&user1), 250);
+
+    // The reward token can't change once rewards have been distributed
+    assert!(token.try_set_reward_token(&token.address).is_err());
+}
+
+#[test]
+fn test_staking() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&admin, &1000);
+    token.mint(&user1, &1000);
+    token.mint(&user2, &1000);
+
+    // Admin funds the reward pool and emits 10 tokens per ledger
+    token.fund_staking_rewards(&admin, &1000);
+    token.set_staking_reward_rate(&10);
+
+    // User 1 stakes 400 tokens, which can no longer be transferred
+    token.stake(&user1, &400);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            user1.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    symbol_short!("stake"),
+                    (&user1, 400_i128).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    assert_eq!(token.staked_balance(&user1), 400);
+    assert_eq!(token.balance(&user1), 600);
+    assert_eq!(token.voting_power(&user1), 1000);
+    assert!(token.try_transfer(&user1, &user2, &601).is_err());
+
+    // User 1 earns everything while staking alone
+    env.ledger().with_mut(|li| li.sequence_number += 10);
+    assert_eq!(token.stake_info(&user1).rewards, 100);
+
+    // User 2 stakes 100 tokens, rewards are split 4:1 from now on
+    token.stake(&user2, &100);
+    env.ledger().with_mut(|li| li.sequence_number += 20);
+    assert_eq!(token.stake_info(&user1).rewards, 260);
+    assert_eq!(token.stake_info(&user2).rewards, 40);
+
+    assert_eq!(token.claim_staking_rewards(&user1), 260);
+    assert_eq!(token.balance(&user1), 860);
+
+    // Unstaked tokens are released after the unbonding period
+    token.unstake(&user1, &400);
+    assert_eq!(token.staked_balance(&user1), 0);
+    assert_eq!(token.voting_power(&user1), 1260);
+    assert!(token.try_withdraw_unstaked(&user1).is_err());
+
+    // Wait out the unbonding period in two steps to keep the contract instance alive
+    env.ledger().with_mut(|li| li.sequence_number += 4 * 17280);
+    assert_eq!(token.balance(&user1), 860);
+    env.ledger().with_mut(|li| li.sequence_number += 3 * 17280);
+    assert_eq!(token.withdraw_unstaked(&user1), 400);
+    assert_eq!(token.balance(&user1), 1260);
+
+    // Emission stops once the reserve is used up
+    assert_eq!(token.stake_info(&user2).rewards, 740);
+}
+
+#[test]
+fn test_extend_ttl() {
+    let env = Env::default();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    env.mock_all_auths();
+    token.mint(&user1, &1000);
+    token.freeze_amount(&user1, &100);
+
+    let balance_ttl = || {
+        env.as_contract(&token.address, || {
+            env.storage()
+                .persistent()
+                .get_ttl(&DataKey::Balance(user1.clone()))
+        })
+    };
+    let frozen_amount_ttl = || {
+        env.as_contract(&token.address, || {
+            env.storage()
+                .persistent()
+                .get_ttl(&DataKey::FrozenAmount(user1.clone()))
+        })
+    };
+    let instance_ttl = || env.as_contract(&token.address, || env.storage().instance().get_ttl());
+
+    // A dormant balance slowly approaches archival
+    env.ledger()
+        .with_mut(|li| li.sequence_number += 2 * DAY_IN_LEDGERS);
+    assert_eq!(balance_ttl(), BALANCE_TTL_FULL - 2 * DAY_IN_LEDGERS);
+    assert_eq!(frozen_amount_ttl(), BALANCE_TTL_FULL - 2 * DAY_IN_LEDGERS);
+
+    // Anyone can bump it without authorization, along with the account's other entries
+    env.set_auths(&[]);
+    token.extend_balance_ttl(&vec![&env, user1.clone(), user2.clone()]);
+    assert_eq!(balance_ttl(), BALANCE_TTL_FULL);
+    assert_eq!(frozen_amount_ttl(), BALANCE_TTL_FULL);
+    assert_eq!(instance_ttl(), INSTANCE_TTL_FULL);
+
+    env.ledger()
+        .with_mut(|li| li.sequence_number += 2 * DAY_IN_LEDGERS);
+    token.extend_instance_ttl();
+    assert_eq!(instance_ttl(), INSTANCE_TTL_FULL);
+
+    let info = token.ttl_info(&user1);
+    assert!(info.has_balance);
+    assert_eq!(info.policy.balance_ttl_full, BALANCE_TTL_FULL);
+    assert!(!token.ttl_info(&user2).has_balance);
+
+    // Reading a balance also keeps the holder's index entries alive
+    env.ledger()
+        .with_mut(|li| li.sequence_number += 2 * DAY_IN_LEDGERS);
+    let position_ttl = || {
+        env.as_contract(&token.address, || {
+            env.storage().persistent().get_ttl(&DataKey::IndexPosition(
+                AddressIndex::Holders,
+                user1.clone(),
+            ))
+        })
+    };
+    assert_eq!(position_ttl(), BALANCE_TTL_FULL - 4 * DAY_IN_LEDGERS);
+    token.balance(&user1);
+    assert_eq!(position_ttl(), BALANCE_TTL_FULL);
+
+    // As does reading a partial freeze
+    assert_eq!(frozen_amount_ttl(), BALANCE_TTL_FULL - 4 * DAY_IN_LEDGERS);
+    assert_eq!(token.frozen_amount(&user1), 100);
+    assert_eq!(frozen_amount_ttl(), BALANCE_TTL_FULL);
+}
+
+#[test]
+fn test_ttl_policy() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    assert_eq!(token.ttl_policy().balance_ttl_full, BALANCE_TTL_FULL);
+
+    let policy = TtlPolicy {
+        instance_ttl_threshold: 10 * DAY_IN_LEDGERS,
+        instance_ttl_full: 14 * DAY_IN_LEDGERS,
+        balance_ttl_threshold: 60 * DAY_IN_LEDGERS,
+        balance_ttl_full: 90 * DAY_IN_LEDGERS,
+    };
+    token.set_ttl_policy(&policy);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    Symbol::new(&env, "set_ttl_policy"),
+                    (policy.clone(),).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    assert_eq!(token.ttl_policy(), policy);
+
+    // New entries and the instance are extended according to the policy
+    token.mint(&user, &1000);
+    env.as_contract(&token.address, || {
+        assert_eq!(
+            env.storage()
+                .persistent()
+                .get_ttl(&DataKey::Balance(user.clone())),
+            90 * DAY_IN_LEDGERS
+        );
+        assert_eq!(env.storage().instance().get_ttl(), 14 * DAY_IN_LEDGERS);
+    });
+
+    // Thresholds must stay below the full TTL, which must fit the network limit
+    let mut invalid = policy.clone();
+    invalid.balance_ttl_threshold = invalid.balance_ttl_full;
+    assert!(token.try_set_ttl_policy(&invalid).is_err());
+
+    let mut invalid = policy.clone();
+    invalid.balance_ttl_full = env.ledger().get().max_entry_ttl;
+    assert!(token.try_set_ttl_policy(&invalid).is_err());
+}
+
+#[test]
+fn test_noop_transfers() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+
+    let has_balance_entry = |account: &Address| {
+        env.as_contract(&token.address, || {
+            env.storage()
+                .persistent()
+                .has(&DataKey::Balance(account.clone()))
+        })
+    };
+
+    // A zero-amount transfer still emits the event but creates no entry
+    token.transfer(&user1, &user2, &0);
+    assert_eq!(env.events().all().len(), 1);
+    assert!(!has_balance_entry(&user2));
+
+    // A self transfer leaves the balance untouched
+    token.transfer(&user1, &user1, &400);
+    assert_eq!(env.events().all().len(), 1);
+    assert_eq!(token.balance(&user1), 1000);
+
+    // Self transfers still require a sufficient balance
+    assert!(token.try_transfer(&user1, &user1, &1001).is_err());
+
+    // Entries are removed once the balance reaches zero
+    token.transfer(&user1, &user2, &1000);
+    assert!(!has_balance_entry(&user1));
+    assert_eq!(token.balance(&user1), 0);
+    assert_eq!(token.balance(&user2), 1000);
+}
+
+#[test]
+fn test_balance_overflow() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user, &i128::MAX);
+
+    assert_eq!(
+        token.try_mint(&user, &1),
+        Err(Ok(TokenError::BalanceOverflow.into()))
+    );
+    assert_eq!(token.balance(&user), i128::MAX);
+}
+
+#[test]
+fn test_transfer_rules() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+    let blocked = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+
+    let rules = env.register(TransferRules, ());
+    TransferRulesClient::new(&env, &rules).set_blocked(&blocked);
+
+    token.set_transfer_rules(&Some(rules.clone()));
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    Symbol::new(&env, "set_transfer_rules"),
+                    (Some(rules.clone()),).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    assert_eq!(token.transfer_rules(), Some(rules));
+
+    // Every balance-moving path is checked against the rules contract
+    token.transfer(&user1, &user2, &100);
+    assert!(token.try_transfer(&user1, &blocked, &100).is_err());
+    assert!(token.try_mint(&blocked, &100).is_err());
+    token.approve(&user1, &user2, &500, &200);
+    assert!(token
+        .try_transfer_from(&user2, &user1, &blocked, &100)
+        .is_err());
+    token.burn(&user1, &100);
+    assert_eq!(token.balance(&user1), 800);
+    assert_eq!(token.balance(&blocked), 0);
+
+    // Removing the rules contract lifts the restrictions
+    token.set_transfer_rules(&None);
+    token.transfer(&user1, &blocked, &100);
+    assert_eq!(token.balance(&blocked), 100);
+}
+
+#[test]
+fn test_holder_limits() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+    let user3 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+    token.transfer(&user1, &user2, &100);
+    assert_eq!(token.holder_count(), 2);
+
+    let limits = HolderLimits {
+        max_holders: Some(2),
+        max_balance_per_account: Some(800),
+    };
+    token.set_holder_limits(&limits);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    Symbol::new(&env, "set_holder_limits"),
+                    (limits.clone(),).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    assert_eq!(token.holder_limits(), limits);
+
+    // A third holder and balances above the cap are rejected on credit
+    assert!(token.try_transfer(&user1, &user3, &100).is_err());
+    assert!(token.try_mint(&user2, &701).is_err());
+    token.mint(&user2, &700);
+    assert_eq!(token.balance(&user2), 800);
+
+    // Emptying an account frees up a holder slot
+    token.burn(&user2, &800);
+    assert_eq!(token.holder_count(), 1);
+    token.transfer(&user1, &user3, &100);
+    assert_eq!(token.holder_count(), 2);
+
+    // Escrowed funds held by the contract don't count as a holder
+    token.stake(&user3, &100);
+    assert_eq!(token.holder_count(), 1);
+}
+
+#[test]
+#[should_panic(expected = "Balance cap must be positive")]
+fn test_holder_limits_invalid_cap() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+
+    // This should panic
+    token.set_holder_limits(&HolderLimits {
+        max_holders: None,
+        max_balance_per_account: Some(0),
+    });
+}
+
+#[test]
+fn test_holders() {
+    // A snapshot of thousands of entries is slow to write and not useful here
+    let env = Env::new_with_config(EnvTestConfig {
+        capture_snapshot_at_drop: false,
+    });
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+
+    let accounts: std::vec::Vec<Address> = (0..2000).map(|_| Address::generate(&env)).collect();
+    // Seed the holders in a single contract frame, thousands of invocations are slow in the test host
+    env.cost_estimate().budget().reset_unlimited();
+    env.as_contract(&token.address, || {
+        for (i, account) in accounts.iter().enumerate() {
+            increase_balance(&env, account.clone(), i as i128 + 1);
+        }
+    });
+    assert_eq!(token.holder_count(), 2000);
+
+    let collect_holders = || {
+        let mut holders = std::collections::BTreeMap::new();
+        let mut start = 0;
+        loop {
+            let page = token.holders(&start, &1000);
+            // Pages are capped regardless of the requested limit
+            assert!(page.len() <= MAX_PAGE_SIZE);
+            if page.is_empty() {
+                break;
+            }
+            for (holder, balance) in page.iter() {
+                assert!(holders
+                    .insert(std::format!("{:?}", holder), balance)
+                    .is_none());
+            }
+            start += page.len();
+        }
+        holders
+    };
+
+    let holders = collect_holders();
+    assert_eq!(holders.len(), 2000);
+    assert_eq!(holders.values().sum::<i128>(), token.total_supply());
+
+    // Emptied accounts leave the index and the remaining holders stay enumerable
+    env.as_contract(&token.address, || {
+        for account in accounts.iter().step_by(3) {
+            let balance = read_balance(&env, account.clone());
+            decrease_balance(&env, account.clone(), balance);
+        }
+    });
+    let holders = collect_holders();
+    assert_eq!(holders.len(), 1333);
+    assert_eq!(token.holder_count(), 1333);
+    assert!(!holders.contains_key(&std::format!("{:?}", accounts[0])));
+    assert_eq!(holders[&std::format!("{:?}", accounts[1])], 2);
+
+    assert!(token.holders(&1333, &10).is_empty());
+}
+
+#[test]
+fn test_frozen_accounts_and_admins() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let signer = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+    let user3 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    assert_eq!(token.admins(), vec![&env, admin.clone()]);
+    assert!(token.frozen_accounts(&0, &10).is_empty());
+
+    token.freeze_account(&user1);
+    token.freeze_account(&user2);
+    token.freeze_account(&user3);
+    // Freezing an already frozen account doesn't list it twice
+    token.freeze_account(&user1);
+    assert_eq!(token.frozen_account_count(), 3);
+    assert_eq!(
+        token.frozen_accounts(&0, &10),
+        vec![
+            &env,
+            (user1.clone(), FREEZE_INDEFINITELY),
+            (user2.clone(), FREEZE_INDEFINITELY),
+            (user3.clone(), FREEZE_INDEFINITELY)
+        ]
+    );
+    assert_eq!(
+        token.frozen_accounts(&1, &1),
+        vec![&env, (user2.clone(), FREEZE_INDEFINITELY)]
+    );
+
+    // Unfrozen accounts leave the list
+    token.unfreeze_account(&user1);
+    token.unfreeze_account(&user1);
+    assert_eq!(token.frozen_account_count(), 2);
+    assert_eq!(
+        token.frozen_accounts(&0, &10),
+        vec![
+            &env,
+            (user3.clone(), FREEZE_INDEFINITELY),
+            (user2.clone(), FREEZE_INDEFINITELY)
+        ]
+    );
+
+    // The admin list follows the council
+    token.set_council(&vec![&env, admin.clone(), signer.clone()], &2);
+    assert_eq!(token.admins(), vec![&env, admin.clone(), signer.clone()]);
+}
+
+#[test]
+fn test_freeze_amount() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+
+    token.freeze_amount(&user1, &600);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    Symbol::new(&env, "freeze_amount"),
+                    (&user1, 600_i128).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+    token.freeze_amount(&user1, &100);
+    assert_eq!(token.frozen_amount(&user1), 700);
+
+    // Debits can't reach into the frozen amount
+    token.transfer(&user1, &user2, &300);
+    assert!(token.try_transfer(&user1, &user2, &1).is_err());
+    assert!(token.try_burn(&user1, &1).is_err());
+    token.approve(&user1, &user2, &100, &200);
+    assert!(token.try_transfer_from(&user2, &user1, &user2, &1).is_err());
+
+    // Credits are still accepted and can be spent
+    token.transfer(&user2, &user1, &100);
+    token.burn(&user1, &100);
+
+    token.unfreeze_amount(&user1, &200);
+    assert_eq!(token.frozen_amount(&user1), 500);
+    token.transfer(&user1, &user2, &200);
+    assert_eq!(token.balance(&user1), 500);
+
+    // Freezing more than the balance or unfreezing more than is frozen fails
+    assert!(token.try_freeze_amount(&user1, &1).is_err());
+    assert!(token.try_unfreeze_amount(&user1, &501).is_err());
+}
+
+#[test]
+fn test_unfreeze_amount_after_rebase() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user, &1000);
+    token.freeze_amount(&user, &1000);
+
+    // A negative rebase leaves less balance than is frozen
+    token.rebase(&950_000_000);
+    assert_eq!(token.balance(&user), 950);
+    assert_eq!(token.frozen_amount(&user), 1000);
+
+    // The freeze can still be reduced, but not grown
+    token.unfreeze_amount(&user, &10);
+    assert_eq!(token.frozen_amount(&user), 990);
+    assert!(token.try_freeze_amount(&user, &1).is_err());
+}
+
+#[test]
+fn test_freeze_account_until() {
+    let env = Env::default();
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let user1 = Address::generate(&env);
+    let user2 = Address::generate(&env);
+
+    let token = create_token(&env, &admin, 18, "Osi Token", "OSI");
+    token.mint(&user1, &1000);
+
+    env.ledger().set_sequence_number(100);
+    token.freeze_account_until(&user1, &150);
+    assert_eq!(
+        env.auths(),
+        std::vec![(
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    token.address.clone(),
+                    Symbol::new(&env, "freeze_account_until"),
+                    (&user1, 150_u32).into_val(&env),
+                )),
+                sub_invocations: std::vec![]
+            }
+        )]
+    );
+
+    // The freeze event carries the expiry
+    assert_eq!(
+        env.events().all(),
+        vec![
+            &env,
+            (
+                token.address.clone(),
+                (String::from_str(&env, "freeze_account"), &admin, &user1).into_val(&env),
+                150_u32.into_val(&env),
+            ),
+        ]
+    );
+
+    assert_eq!(token.freeze_remaining(&user1), 51);
+    assert_eq!(
+        token.frozen_accounts(&0, &10),
+        vec![&env, (user1.clone(), 150)]
+    );
+    assert!(token.try_transfer(&user1, &user2, &100).is_err());
+
+    // The freeze still applies on its last ledger and is lifted afterwards
+    env.ledger().set_sequence_number(150);
+    assert_eq!(token.freeze_remaining(&user1), 1);
+    assert!(token.try_transfer(&user1, &user2, &100).is_err());
+
+    env.ledger().set_sequence_number(151);
+    assert_eq!(token.freeze_remaining(&user1), 0);
+
+    // The expired freeze stays listed with its expiry until the account is used again
+    assert_eq!(
+        token.frozen_accounts(&0, &10),
+        vec![&env, (user1.clone(), 150)]
+    );
+    token.transfer(&user1, &user2, &100);
+    assert!(token.frozen_accounts(&0, &10).is_empty());
+    assert_eq!(token.frozen_account_count(), 0);
+
+    // Refreezing replaces the expired freeze instead of listing the account twice
+    token.freeze_account(&user1);
+    assert_eq!(token.freeze_remaining(&user1), FREEZE_INDEFINITELY);
+    assert_eq!(
+        token.frozen_accounts(&0, &10),
+        vec![&env, (user1.clone(), FREEZE_INDEFINITELY)]
+    );
+
+    // An expiry in the past is rejected
+    assert!(token.try_freeze_account_until(&user2, &150).is_err());
 }
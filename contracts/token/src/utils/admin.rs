@@ -1,17 +1,69 @@
-use crate::storage::DataKey;
-use soroban_sdk::{Address, Env};
+use crate::storage::{AdminCouncil, DataKey};
+use soroban_sdk::{vec, Address, Env};
 
 pub fn has_admin(env: &Env) -> bool {
-    let key = DataKey::Admin;
+    let key = DataKey::Council;
     env.storage().instance().has(&key)
 }
 
-pub fn read_admin(env: &Env) -> Address {
-    let key = DataKey::Admin;
+pub fn read_council(env: &Env) -> AdminCouncil {
+    let key = DataKey::Council;
     env.storage().instance().get(&key).unwrap()
 }
 
+pub fn write_council(env: &Env, council: &AdminCouncil) {
+    if council.signers.is_empty() {
+        panic!("Council must have at least one signer");
+    }
+
+    if council.threshold == 0 || council.threshold > council.signers.len() {
+        panic!("Threshold must be between 1 and the number of signers");
+    }
+
+    for (i, signer) in council.signers.iter().enumerate() {
+        if council.signers.first_index_of(&signer) != Some(i as u32) {
+            panic!("Duplicate signer");
+        }
+    }
+
+    let key = DataKey::Council;
+    env.storage().instance().set(&key, council);
+
+    let key = DataKey::CouncilEpoch;
+    env.storage()
+        .instance()
+        .set(&key, &(read_council_epoch(env) + 1));
+}
+
+// Incremented on every council change so proposals made under a previous council go stale
+pub fn read_council_epoch(env: &Env) -> u32 {
+    let key = DataKey::CouncilEpoch;
+    env.storage().instance().get(&key).unwrap_or(0)
+}
+
 pub fn write_admin(env: &Env, address: &Address) {
-    let key = DataKey::Admin;
-    env.storage().instance().set(&key, address);
+    write_council(
+        env,
+        &AdminCouncil {
+            signers: vec![env, address.clone()],
+            threshold: 1,
+        },
+    );
+}
+
+pub fn is_signer(env: &Env, address: &Address) -> bool {
+    read_council(env).signers.contains(address)
+}
+
+// Direct admin entry points are only available while the council consists of
+// a single signer. Otherwise admin operations must go through proposals.
+pub fn require_admin(env: &Env) -> Address {
+    let council = read_council(env);
+    if council.signers.len() != 1 {
+        panic!("Admin operations require council approval");
+    }
+
+    let admin = council.signers.get(0).unwrap();
+    admin.require_auth();
+    admin
 }
@@ -1,5 +1,9 @@
-use crate::storage::{AllowanceData, AllowanceDataKey, DataKey};
-use soroban_sdk::{Address, Env};
+use crate::{
+    constants::UNLIMITED_ALLOWANCE,
+    storage::{AllowanceData, AllowanceDataKey, DataKey},
+    utils::ttl::extend_persistent_ttl,
+};
+use soroban_sdk::{Address, Env, Vec};
 
 pub fn read_allowance(env: &Env, owner: Address, spender: Address) -> AllowanceData {
     let key = DataKey::Allowance(AllowanceDataKey { owner, spender });
@@ -20,6 +24,11 @@ pub fn read_allowance(env: &Env, owner: Address, spender: Address) -> AllowanceD
     }
 }
 
+// Allowances live in temporary storage, so they can't outlive the network's maximum TTL
+pub fn max_allowance_expiration(env: &Env) -> u32 {
+    env.ledger().sequence() + env.storage().max_ttl()
+}
+
 pub fn write_allowance(
     env: &Env,
     owner: Address,
@@ -36,6 +45,17 @@ pub fn write_allowance(
         panic!("expiration_ledgers can't be less than the current ledgers when amount > 0");
     }
 
+    if amount > 0 && expiration_ledgers > max_allowance_expiration(env) {
+        panic!("expiration_ledgers can't be greater than the maximum allowance expiration");
+    }
+
+    // Every live allowance is indexed, so the index only changes when an allowance starts or
+    // stops being live. Allowances that expire on their own are pruned when the index is read.
+    let was_active = read_allowance(env, owner.clone(), spender.clone()).amount > 0;
+    if was_active != (amount > 0) {
+        update_spenders(env, owner.clone(), spender.clone(), amount > 0);
+    }
+
     let key = DataKey::Allowance(AllowanceDataKey { owner, spender });
     env.storage().temporary().set(&key, &allowance);
 
@@ -50,6 +70,89 @@ pub fn write_allowance(
     }
 }
 
+pub fn read_spenders(env: &Env, owner: Address) -> Vec<Address> {
+    let key = DataKey::Spenders(owner);
+    if let Some(spenders) = env
+        .storage()
+        .persistent()
+        .get::<DataKey, Vec<Address>>(&key)
+    {
+        extend_persistent_ttl(env, &key);
+
+        spenders
+    } else {
+        Vec::new(env)
+    }
+}
+
+fn update_spenders(env: &Env, owner: Address, spender: Address, active: bool) {
+    let mut spenders = read_spenders(env, owner.clone());
+    let index = spenders.first_index_of(&spender);
+
+    match (index, active) {
+        (None, true) => spenders.push_back(spender),
+        (Some(i), false) => {
+            spenders.remove(i);
+        }
+        _ => return,
+    }
+
+    write_spenders(env, owner, &spenders);
+}
+
+fn write_spenders(env: &Env, owner: Address, spenders: &Vec<Address>) {
+    let key = DataKey::Spenders(owner);
+    if spenders.is_empty() {
+        env.storage().persistent().remove(&key);
+    } else {
+        env.storage().persistent().set(&key, spenders);
+
+        extend_persistent_ttl(env, &key);
+    }
+}
+
+// Live allowances of an owner as `(spender, amount, expiration_ledgers)`. Spenders whose
+// allowance has expired are dropped from the index.
+pub fn read_active_allowances(env: &Env, owner: Address) -> Vec<(Address, i128, u32)> {
+    let spenders = read_spenders(env, owner.clone());
+
+    let mut allowances = Vec::new(env);
+    let mut active = Vec::new(env);
+    for spender in spenders.iter() {
+        let allowance = read_allowance(env, owner.clone(), spender.clone());
+        if allowance.amount > 0 {
+            active.push_back(spender.clone());
+            allowances.push_back((spender, allowance.amount, allowance.expiration_ledgers));
+        }
+    }
+
+    if active.len() != spenders.len() {
+        write_spenders(env, owner, &active);
+    }
+    allowances
+}
+
+// Zeroes every live allowance of an owner and clears the index
+pub fn revoke_allowances(env: &Env, owner: Address) -> Vec<(Address, i128, u32)> {
+    let allowances = read_active_allowances(env, owner.clone());
+    for (spender, _, expiration_ledgers) in allowances.iter() {
+        let key = DataKey::Allowance(AllowanceDataKey {
+            owner: owner.clone(),
+            spender,
+        });
+        env.storage().temporary().set(
+            &key,
+            &AllowanceData {
+                amount: 0,
+                expiration_ledgers,
+            },
+        );
+    }
+
+    write_spenders(env, owner, &Vec::new(env));
+    allowances
+}
+
 pub fn spend_allowance(env: &Env, owner: Address, spender: Address, spend_amount: i128) {
     let allowance = read_allowance(env, owner.clone(), spender.clone());
 
@@ -57,7 +160,7 @@ pub fn spend_allowance(env: &Env, owner: Address, spender: Address, spend_amount
         panic!("Insufficient allowance");
     }
 
-    if spend_amount == 0 {
+    if spend_amount == 0 || allowance.amount == UNLIMITED_ALLOWANCE {
         return;
     }
 
@@ -1,42 +1,160 @@
 use crate::{
-    constants::{BALANCE_TTL_FULL, BALANCE_TTL_THRESHOLD},
-    storage::DataKey,
+    errors::TokenError,
+    storage::{AddressIndex, DataKey},
+    utils::{
+        holders::{assert_within_balance_cap, on_holder_added, on_holder_removed},
+        index::extend_index_ttl,
+        rebase::{to_amount, to_shares},
+        rewards::{on_shares_credited, on_shares_debited},
+        supply::{decrease_total_shares, increase_total_shares},
+        ttl::extend_persistent_ttl,
+    },
 };
-use soroban_sdk::{Address, Env};
+use soroban_sdk::{panic_with_error, Address, Env};
 
-pub fn read_balance(env: &Env, address: Address) -> i128 {
-    let key = DataKey::Balance(address);
-    if let Some(balance) = env.storage().persistent().get::<DataKey, i128>(&key) {
-        env.storage()
-            .persistent()
-            .extend_ttl(&key, BALANCE_TTL_THRESHOLD, BALANCE_TTL_FULL);
+// Balances are stored as shares, which equal token amounts until the first rebase. The
+// holder's index entries are kept alive alongside the balance.
+pub fn read_shares(env: &Env, address: Address) -> i128 {
+    let key = DataKey::Balance(address.clone());
+    if let Some(shares) = env.storage().persistent().get::<DataKey, i128>(&key) {
+        extend_persistent_ttl(env, &key);
+        extend_index_ttl(env, &AddressIndex::Holders, &address);
 
-        balance
+        shares
     } else {
         0
     }
 }
 
-fn write_balance(env: &Env, address: Address, amount: i128) {
-    let key = DataKey::Balance(address);
-    env.storage().persistent().set(&key, &amount);
+// Emptied balances are removed rather than stored as zero, so they stop paying rent
+fn write_shares(env: &Env, address: Address, shares: i128) {
+    let key = DataKey::Balance(address.clone());
+    if shares == 0 {
+        env.storage().persistent().remove(&key);
+        return;
+    }
+
+    env.storage().persistent().set(&key, &shares);
+
+    extend_persistent_ttl(env, &key);
+    extend_index_ttl(env, &AddressIndex::Holders, &address);
+}
+
+// Extends the TTL of an existing balance entry without reading it
+pub fn extend_balance_ttl(env: &Env, address: Address) {
+    let key = DataKey::Balance(address.clone());
+    if !env.storage().persistent().has(&key) {
+        return;
+    }
+
+    extend_persistent_ttl(env, &key);
+    extend_index_ttl(env, &AddressIndex::Holders, &address);
+}
+
+// The contract's own balance backs escrows (streams, HTLCs, stakes and the staking and reward
+// pools) whose records hold plain amounts. It is stored as an amount and doesn't rebase, so
+// those records stay fully backed whatever the index does.
+fn is_escrow(env: &Env, address: &Address) -> bool {
+    *address == env.current_contract_address()
+}
+
+fn shares_to_amount(env: &Env, address: &Address, shares: i128) -> i128 {
+    if is_escrow(env, address) {
+        shares
+    } else {
+        to_amount(env, shares)
+    }
+}
+
+fn amount_to_shares(env: &Env, address: &Address, amount: i128, round_up: bool) -> i128 {
+    if is_escrow(env, address) {
+        amount
+    } else {
+        to_shares(env, amount, round_up)
+    }
+}
 
-    env.storage()
-        .persistent()
-        .extend_ttl(&key, BALANCE_TTL_THRESHOLD, BALANCE_TTL_FULL);
+pub fn read_balance(env: &Env, address: Address) -> i128 {
+    let shares = read_shares(env, address.clone());
+    shares_to_amount(env, &address, shares)
 }
 
 pub fn increase_balance(env: &Env, address: Address, amount: i128) {
-    let balance = read_balance(env, address.clone());
-    write_balance(env, address, balance + amount);
+    let delta = amount_to_shares(env, &address, amount, false);
+    credit_shares(env, address, delta);
 }
 
 pub fn decrease_balance(env: &Env, address: Address, amount: i128) {
-    let balance = read_balance(env, address.clone());
+    let delta = amount_to_shares(env, &address, amount, true);
+    debit_shares(env, address, amount, delta);
+}
+
+// Zero-amount and self transfers only check the balance and leave storage untouched.
+// Otherwise the shares debited from the sender are exactly the shares credited to the
+// recipient, so transfers between holders never change the total supply. Transfers into or
+// out of escrow convert between shares and amounts, rounding against the holder.
+pub fn transfer_balance(env: &Env, from: Address, to: Address, amount: i128) {
+    if amount == 0 || from == to {
+        if read_balance(env, from) < amount {
+            panic!("Insufficient balance");
+        }
+        return;
+    }
+
+    if is_escrow(env, &from) || is_escrow(env, &to) {
+        decrease_balance(env, from, amount);
+        increase_balance(env, to, amount);
+        return;
+    }
+
+    let delta = to_shares(env, amount, true);
+    debit_shares(env, from, amount, delta);
+    credit_shares(env, to, delta);
+}
+
+fn credit_shares(env: &Env, address: Address, delta: i128) {
+    if delta == 0 {
+        return;
+    }
+
+    let old_shares = read_shares(env, address.clone());
+    let shares = old_shares
+        .checked_add(delta)
+        .unwrap_or_else(|| panic_with_error!(env, TokenError::BalanceOverflow));
+    assert_within_balance_cap(env, &address, shares_to_amount(env, &address, shares));
+    write_shares(env, address.clone(), shares);
+
+    if !is_escrow(env, &address) {
+        increase_total_shares(env, delta);
+    }
+
+    if old_shares == 0 {
+        on_holder_added(env, &address);
+    }
+
+    on_shares_credited(env, address, delta);
+}
 
-    if balance < amount {
+fn debit_shares(env: &Env, address: Address, amount: i128, delta: i128) {
+    let shares = read_shares(env, address.clone());
+
+    if shares_to_amount(env, &address, shares) < amount {
         panic!("Insufficient balance");
     }
 
-    write_balance(env, address, balance - amount);
+    if delta == 0 {
+        return;
+    }
+
+    write_shares(env, address.clone(), shares - delta);
+
+    if !is_escrow(env, &address) {
+        decrease_total_shares(env, delta);
+    }
+
+    if shares == delta {
+        on_holder_removed(env, &address);
+    }
+
+    on_shares_debited(env, address, delta);
 }
@@ -0,0 +1,94 @@
+use crate::{
+    constants::FREEZE_INDEFINITELY,
+    storage::{AddressIndex, DataKey},
+    utils::{
+        balance::read_balance,
+        index::{index_insert, index_remove},
+        ttl::extend_persistent_ttl,
+    },
+};
+use soroban_sdk::{Address, Env};
+
+// A freeze is stored as the last ledger it applies to. Expired freezes stay stored until the
+// account is next checked, unfrozen or frozen again.
+pub fn read_frozen_until(env: &Env, account: &Address) -> Option<u32> {
+    let key = DataKey::Frozen(account.clone());
+    env.storage().instance().get(&key)
+}
+
+pub fn is_frozen(env: &Env, account: &Address) -> bool {
+    read_frozen_until(env, account).is_some_and(|until| env.ledger().sequence() <= until)
+}
+
+// Remaining ledgers of the freeze including the current one, `FREEZE_INDEFINITELY` if it
+// doesn't expire
+pub fn freeze_remaining(env: &Env, account: &Address) -> u32 {
+    match read_frozen_until(env, account) {
+        Some(FREEZE_INDEFINITELY) => FREEZE_INDEFINITELY,
+        Some(until) if env.ledger().sequence() <= until => until - env.ledger().sequence() + 1,
+        _ => 0,
+    }
+}
+
+// Frozen accounts are also kept in an index so they can be enumerated
+pub fn write_frozen(env: &Env, account: &Address, until: Option<u32>) {
+    let key = DataKey::Frozen(account.clone());
+    let was_stored = env.storage().instance().has(&key);
+
+    match until {
+        Some(until) => {
+            if until < env.ledger().sequence() {
+                panic!("Freeze expiry can't be in the past");
+            }
+
+            env.storage().instance().set(&key, &until);
+            if !was_stored {
+                index_insert(env, &AddressIndex::Frozen, account);
+            }
+        }
+        None => {
+            if was_stored {
+                env.storage().instance().remove(&key);
+                index_remove(env, &AddressIndex::Frozen, account);
+            }
+        }
+    }
+}
+
+pub fn assert_account_not_frozen(env: &Env, account: &Address) {
+    if is_frozen(env, account) {
+        panic!("Account is frozen");
+    }
+
+    // A lapsed freeze is dropped from storage and the index once the account is checked
+    write_frozen(env, account, None);
+}
+
+pub fn read_frozen_amount(env: &Env, account: &Address) -> i128 {
+    let key = DataKey::FrozenAmount(account.clone());
+    if let Some(amount) = env.storage().persistent().get::<DataKey, i128>(&key) {
+        extend_persistent_ttl(env, &key);
+
+        amount
+    } else {
+        0
+    }
+}
+
+pub fn write_frozen_amount(env: &Env, account: &Address, amount: i128) {
+    let key = DataKey::FrozenAmount(account.clone());
+    if amount == 0 {
+        env.storage().persistent().remove(&key);
+        return;
+    }
+
+    env.storage().persistent().set(&key, &amount);
+    extend_persistent_ttl(env, &key);
+}
+
+pub fn assert_frozen_amount_kept(env: &Env, account: &Address, debit: i128) {
+    let frozen_amount = read_frozen_amount(env, account);
+    if frozen_amount > 0 && read_balance(env, account.clone()) - debit < frozen_amount {
+        panic!("Amount is frozen");
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{
+    storage::{AddressIndex, DataKey, HolderLimits},
+    utils::index::{index_insert, index_len, index_remove},
+};
+use soroban_sdk::{Address, Env};
+
+// Accounts with a non-zero balance are kept in the holders index. This contract's own
+// address holds escrowed funds, so it is neither indexed nor capped.
+
+pub fn read_holder_count(env: &Env) -> u32 {
+    index_len(env, &AddressIndex::Holders)
+}
+
+pub fn read_holder_limits(env: &Env) -> HolderLimits {
+    let key = DataKey::HolderLimits;
+    env.storage().instance().get(&key).unwrap_or(HolderLimits {
+        max_holders: None,
+        max_balance_per_account: None,
+    })
+}
+
+pub fn write_holder_limits(env: &Env, limits: &HolderLimits) {
+    if limits.max_balance_per_account.is_some_and(|cap| cap <= 0) {
+        panic!("Balance cap must be positive");
+    }
+
+    let key = DataKey::HolderLimits;
+    env.storage().instance().set(&key, limits);
+}
+
+pub fn assert_within_balance_cap(env: &Env, account: &Address, balance: i128) {
+    if *account == env.current_contract_address() {
+        return;
+    }
+
+    if let Some(cap) = read_holder_limits(env).max_balance_per_account {
+        if balance > cap {
+            panic!("Balance exceeds the per-account cap");
+        }
+    }
+}
+
+pub fn on_holder_added(env: &Env, account: &Address) {
+    if *account == env.current_contract_address() {
+        return;
+    }
+
+    let count = read_holder_count(env) + 1;
+    if let Some(max_holders) = read_holder_limits(env).max_holders {
+        if count > max_holders {
+            panic!("Holder limit reached");
+        }
+    }
+
+    index_insert(env, &AddressIndex::Holders, account);
+}
+
+pub fn on_holder_removed(env: &Env, account: &Address) {
+    if *account == env.current_contract_address() {
+        return;
+    }
+
+    index_remove(env, &AddressIndex::Holders, account);
+}
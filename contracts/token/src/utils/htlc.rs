@@ -0,0 +1,34 @@
+use crate::{
+    storage::{DataKey, Htlc},
+    utils::ttl::extend_persistent_ttl,
+};
+use soroban_sdk::Env;
+
+pub fn read_htlc(env: &Env, id: u32) -> Htlc {
+    let key = DataKey::Htlc(id);
+    if let Some(htlc) = env.storage().persistent().get::<DataKey, Htlc>(&key) {
+        extend_persistent_ttl(env, &key);
+
+        htlc
+    } else {
+        panic!("HTLC not found");
+    }
+}
+
+pub fn remove_htlc(env: &Env, id: u32) {
+    let key = DataKey::Htlc(id);
+    env.storage().persistent().remove(&key);
+}
+
+pub fn create_htlc(env: &Env, htlc: &Htlc) -> u32 {
+    let key = DataKey::HtlcCount;
+    let id = env.storage().instance().get::<_, u32>(&key).unwrap_or(0);
+    env.storage().instance().set(&key, &(id + 1));
+
+    let key = DataKey::Htlc(id);
+    env.storage().persistent().set(&key, htlc);
+
+    extend_persistent_ttl(env, &key);
+
+    id
+}
@@ -0,0 +1,110 @@
+use crate::{
+    constants::{INDEX_CHUNK_SIZE, MAX_PAGE_SIZE},
+    storage::{AddressIndex, DataKey},
+    utils::ttl::extend_persistent_ttl,
+};
+use soroban_sdk::{Address, Env, Vec};
+
+// Enumerable sets of addresses. Members are stored by position in fixed-size chunks in
+// persistent storage, with a reverse lookup per member, so enumeration never grows the
+// instance entry and a page only touches a couple of chunks. Removal moves the last member
+// into the freed position, which keeps positions dense but not in insertion order.
+
+pub fn index_len(env: &Env, index: &AddressIndex) -> u32 {
+    let key = DataKey::IndexLen(index.clone());
+    env.storage().instance().get(&key).unwrap_or(0)
+}
+
+fn write_index_len(env: &Env, index: &AddressIndex, len: u32) {
+    let key = DataKey::IndexLen(index.clone());
+    env.storage().instance().set(&key, &len);
+}
+
+// The account must not already be a member
+pub fn index_insert(env: &Env, index: &AddressIndex, account: &Address) {
+    let position = index_len(env, index);
+    write_index_len(env, index, position + 1);
+
+    let mut chunk = read_chunk(env, index, position / INDEX_CHUNK_SIZE);
+    chunk.push_back(account.clone());
+    write_chunk(env, index, position / INDEX_CHUNK_SIZE, &chunk);
+    write_position(env, index, account, position);
+}
+
+// The account must be a member
+pub fn index_remove(env: &Env, index: &AddressIndex, account: &Address) {
+    let key = DataKey::IndexPosition(index.clone(), account.clone());
+    let position: u32 = env.storage().persistent().get(&key).unwrap();
+    env.storage().persistent().remove(&key);
+
+    let last = index_len(env, index) - 1;
+    let mut last_chunk = read_chunk(env, index, last / INDEX_CHUNK_SIZE);
+    let moved = last_chunk.pop_back_unchecked();
+
+    if position != last {
+        if position / INDEX_CHUNK_SIZE == last / INDEX_CHUNK_SIZE {
+            last_chunk.set(position % INDEX_CHUNK_SIZE, moved.clone());
+        } else {
+            let mut chunk = read_chunk(env, index, position / INDEX_CHUNK_SIZE);
+            chunk.set(position % INDEX_CHUNK_SIZE, moved.clone());
+            write_chunk(env, index, position / INDEX_CHUNK_SIZE, &chunk);
+        }
+        write_position(env, index, &moved, position);
+    }
+    write_chunk(env, index, last / INDEX_CHUNK_SIZE, &last_chunk);
+
+    write_index_len(env, index, last);
+}
+
+// Returns up to `limit` members starting at position `start`, capped at `MAX_PAGE_SIZE`
+pub fn read_index_page(env: &Env, index: &AddressIndex, start: u32, limit: u32) -> Vec<Address> {
+    let end = index_len(env, index).min(start.saturating_add(limit.min(MAX_PAGE_SIZE)));
+
+    let mut members = Vec::new(env);
+    let mut position = start;
+    while position < end {
+        let chunk_start = position / INDEX_CHUNK_SIZE * INDEX_CHUNK_SIZE;
+        let chunk = read_chunk(env, index, position / INDEX_CHUNK_SIZE);
+        let chunk_end = chunk.len().min(end - chunk_start);
+        members.append(&chunk.slice(position - chunk_start..chunk_end));
+        position = chunk_start + INDEX_CHUNK_SIZE;
+    }
+    members
+}
+
+// Keeps the index entries of a member alive, e.g. along with its balance
+pub fn extend_index_ttl(env: &Env, index: &AddressIndex, account: &Address) {
+    let key = DataKey::IndexPosition(index.clone(), account.clone());
+    if let Some(position) = env.storage().persistent().get::<_, u32>(&key) {
+        extend_persistent_ttl(env, &key);
+        extend_persistent_ttl(
+            env,
+            &DataKey::IndexChunk(index.clone(), position / INDEX_CHUNK_SIZE),
+        );
+    }
+}
+
+fn read_chunk(env: &Env, index: &AddressIndex, chunk: u32) -> Vec<Address> {
+    let key = DataKey::IndexChunk(index.clone(), chunk);
+    env.storage()
+        .persistent()
+        .get(&key)
+        .unwrap_or(Vec::new(env))
+}
+
+fn write_chunk(env: &Env, index: &AddressIndex, chunk: u32, members: &Vec<Address>) {
+    let key = DataKey::IndexChunk(index.clone(), chunk);
+    if members.is_empty() {
+        env.storage().persistent().remove(&key);
+        return;
+    }
+
+    env.storage().persistent().set(&key, members);
+    extend_persistent_ttl(env, &key);
+}
+
+fn write_position(env: &Env, index: &AddressIndex, account: &Address, position: u32) {
+    let key = DataKey::IndexPosition(index.clone(), account.clone());
+    env.storage().persistent().set(&key, &position);
+    extend_persistent_ttl(env, &key);
+}
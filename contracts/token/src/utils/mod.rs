@@ -1,4 +1,19 @@
 pub mod admin;
 pub mod allowance;
 pub mod balance;
+pub mod freeze;
+pub mod holders;
+pub mod htlc;
+pub mod index;
 pub mod metadata;
+pub mod proposal;
+pub mod rebase;
+pub mod rewards;
+pub mod rules;
+pub mod staking;
+pub mod stream;
+pub mod subscription;
+pub mod supply;
+pub mod transfer;
+pub mod ttl;
+pub mod wrapper;
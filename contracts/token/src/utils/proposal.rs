@@ -0,0 +1,68 @@
+use crate::{
+    constants::PROPOSAL_LIFETIME_LEDGERS,
+    storage::{AdminAction, DataKey, Proposal},
+    utils::{
+        admin::{read_council, read_council_epoch},
+        ttl::extend_persistent_ttl,
+    },
+};
+use soroban_sdk::{vec, Address, Env};
+
+pub fn read_proposal(env: &Env, id: u32) -> Proposal {
+    let key = DataKey::Proposal(id);
+    if let Some(proposal) = env.storage().persistent().get::<DataKey, Proposal>(&key) {
+        extend_persistent_ttl(env, &key);
+
+        proposal
+    } else {
+        panic!("Proposal not found");
+    }
+}
+
+pub fn write_proposal(env: &Env, id: u32, proposal: &Proposal) {
+    let key = DataKey::Proposal(id);
+    env.storage().persistent().set(&key, proposal);
+    extend_persistent_ttl(env, &key);
+}
+
+pub fn create_proposal(env: &Env, proposer: Address, action: AdminAction) -> (u32, Proposal) {
+    let key = DataKey::ProposalCount;
+    let id = env.storage().instance().get::<_, u32>(&key).unwrap_or(0);
+    env.storage().instance().set(&key, &(id + 1));
+
+    let proposal = Proposal {
+        proposer: proposer.clone(),
+        action,
+        approvals: vec![env, proposer],
+        executed: false,
+        council_epoch: read_council_epoch(env),
+        expiration_ledger: env.ledger().sequence() + PROPOSAL_LIFETIME_LEDGERS,
+    };
+    write_proposal(env, id, &proposal);
+
+    (id, proposal)
+}
+
+// Proposals can only be approved until they expire and while the council that received them
+// is unchanged
+pub fn assert_proposal_active(env: &Env, proposal: &Proposal) {
+    if proposal.council_epoch != read_council_epoch(env) {
+        panic!("Proposal was made under a previous council");
+    }
+
+    if env.ledger().sequence() > proposal.expiration_ledger {
+        panic!("Proposal expired");
+    }
+}
+
+// Approvals from signers that have since been removed from the council do not count.
+pub fn is_approved(env: &Env, proposal: &Proposal) -> bool {
+    let council = read_council(env);
+    let approvals = proposal
+        .approvals
+        .iter()
+        .filter(|signer| council.signers.contains(signer))
+        .count() as u32;
+
+    approvals >= council.threshold
+}
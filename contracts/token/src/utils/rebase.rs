@@ -0,0 +1,58 @@
+use crate::{
+    constants::{MAX_REBASE_CHANGE_BPS, REBASE_INDEX_PRECISION},
+    storage::DataKey,
+};
+use soroban_sdk::Env;
+
+// Computes `a * b / c` for non-negative operands without overflowing on the intermediate product
+fn mul_div(a: i128, b: i128, c: i128, round_up: bool) -> i128 {
+    let remainder = a % c * b;
+    let result = a / c * b + remainder / c;
+    if round_up && remainder % c != 0 {
+        result + 1
+    } else {
+        result
+    }
+}
+
+pub fn read_index(env: &Env) -> i128 {
+    let key = DataKey::RebaseIndex;
+    env.storage()
+        .instance()
+        .get(&key)
+        .unwrap_or(REBASE_INDEX_PRECISION)
+}
+
+pub fn write_index(env: &Env, index: i128) {
+    let old_index = read_index(env);
+
+    if index <= 0 {
+        panic!("Rebase index must be positive");
+    }
+
+    if (index - old_index).abs() > mul_div(old_index, MAX_REBASE_CHANGE_BPS, 10_000, false) {
+        panic!("Rebase index change exceeds the limit");
+    }
+
+    let key = DataKey::RebaseIndex;
+    env.storage().instance().set(&key, &index);
+}
+
+// Shares are rounded down when crediting and up when debiting, so rounding never creates tokens
+pub fn to_shares(env: &Env, amount: i128, round_up: bool) -> i128 {
+    let index = read_index(env);
+    if index == REBASE_INDEX_PRECISION {
+        return amount;
+    }
+
+    mul_div(amount, REBASE_INDEX_PRECISION, index, round_up)
+}
+
+pub fn to_amount(env: &Env, shares: i128) -> i128 {
+    let index = read_index(env);
+    if index == REBASE_INDEX_PRECISION {
+        return shares;
+    }
+
+    mul_div(shares, index, REBASE_INDEX_PRECISION, false)
+}
@@ -0,0 +1,182 @@
+use crate::{
+    constants::REWARD_MAGNITUDE,
+    storage::{DataKey, RewardAccount},
+    utils::ttl::extend_persistent_ttl,
+};
+use soroban_sdk::{Address, Env, I256};
+
+// Rewards follow the magnified dividend-per-share scheme. Every balance change adjusts the
+// account's correction term, so a holder only earns from distributions made while holding.
+
+pub fn read_reward_token(env: &Env) -> Option<Address> {
+    let key = DataKey::RewardToken;
+    env.storage().instance().get(&key)
+}
+
+pub fn write_reward_token(env: &Env, token: &Address) {
+    if read_reward_per_share(env) != I256::from_i32(env, 0) {
+        panic!("Rewards already distributed");
+    }
+
+    let key = DataKey::RewardToken;
+    env.storage().instance().set(&key, token);
+}
+
+fn read_reward_per_share(env: &Env) -> I256 {
+    let key = DataKey::RewardPerShare;
+    env.storage()
+        .instance()
+        .get(&key)
+        .unwrap_or(I256::from_i32(env, 0))
+}
+
+fn read_eligible_shares(env: &Env) -> i128 {
+    let key = DataKey::RewardEligibleShares;
+    env.storage().instance().get(&key).unwrap_or(0)
+}
+
+fn write_eligible_shares(env: &Env, shares: i128) {
+    let key = DataKey::RewardEligibleShares;
+    env.storage().instance().set(&key, &shares);
+}
+
+fn read_reward_account(env: &Env, address: Address) -> RewardAccount {
+    let key = DataKey::Rewards(address);
+    if let Some(account) = env
+        .storage()
+        .persistent()
+        .get::<DataKey, RewardAccount>(&key)
+    {
+        extend_persistent_ttl(env, &key);
+
+        account
+    } else {
+        RewardAccount {
+            correction: I256::from_i32(env, 0),
+            withdrawn: 0,
+        }
+    }
+}
+
+fn write_reward_account(env: &Env, address: Address, account: &RewardAccount) {
+    let key = DataKey::Rewards(address);
+    env.storage().persistent().set(&key, account);
+
+    extend_persistent_ttl(env, &key);
+}
+
+// The contract's own balance holds escrowed funds and undistributed rewards, so it never earns
+pub fn is_reward_excluded(env: &Env, address: &Address) -> bool {
+    if *address == env.current_contract_address() {
+        return true;
+    }
+
+    let key = DataKey::RewardExcluded(address.clone());
+    if env.storage().persistent().has(&key) {
+        extend_persistent_ttl(env, &key);
+
+        true
+    } else {
+        false
+    }
+}
+
+pub fn write_reward_excluded(env: &Env, address: Address, excluded: bool, shares: i128) {
+    if is_reward_excluded(env, &address) == excluded {
+        return;
+    }
+
+    // Exclusion works like moving all of the account's shares out of the reward pool,
+    // which keeps the rewards earned so far withdrawable
+    if excluded {
+        on_shares_debited(env, address.clone(), shares);
+    }
+
+    let key = DataKey::RewardExcluded(address.clone());
+    if excluded {
+        env.storage().persistent().set(&key, &true);
+        extend_persistent_ttl(env, &key);
+    } else {
+        env.storage().persistent().remove(&key);
+    }
+
+    if !excluded {
+        on_shares_credited(env, address, shares);
+    }
+}
+
+pub fn on_shares_credited(env: &Env, address: Address, shares: i128) {
+    if shares == 0 || is_reward_excluded(env, &address) {
+        return;
+    }
+
+    write_eligible_shares(env, read_eligible_shares(env) + shares);
+
+    let reward_per_share = read_reward_per_share(env);
+    if reward_per_share == I256::from_i32(env, 0) {
+        return;
+    }
+
+    let mut account = read_reward_account(env, address.clone());
+    let delta = reward_per_share.mul(&I256::from_i128(env, shares));
+    account.correction = account.correction.sub(&delta);
+    write_reward_account(env, address, &account);
+}
+
+pub fn on_shares_debited(env: &Env, address: Address, shares: i128) {
+    if shares == 0 || is_reward_excluded(env, &address) {
+        return;
+    }
+
+    write_eligible_shares(env, read_eligible_shares(env) - shares);
+
+    let reward_per_share = read_reward_per_share(env);
+    if reward_per_share == I256::from_i32(env, 0) {
+        return;
+    }
+
+    let mut account = read_reward_account(env, address.clone());
+    let delta = reward_per_share.mul(&I256::from_i128(env, shares));
+    account.correction = account.correction.add(&delta);
+    write_reward_account(env, address, &account);
+}
+
+pub fn add_rewards(env: &Env, amount: i128) {
+    let eligible_shares = read_eligible_shares(env);
+    if eligible_shares == 0 {
+        panic!("No eligible holders");
+    }
+
+    let increment = I256::from_i128(env, amount)
+        .mul(&I256::from_i128(env, REWARD_MAGNITUDE))
+        .div(&I256::from_i128(env, eligible_shares));
+
+    let key = DataKey::RewardPerShare;
+    env.storage()
+        .instance()
+        .set(&key, &read_reward_per_share(env).add(&increment));
+}
+
+pub fn withdrawable_rewards(env: &Env, address: Address, shares: i128) -> i128 {
+    let shares = if is_reward_excluded(env, &address) {
+        0
+    } else {
+        shares
+    };
+
+    let account = read_reward_account(env, address);
+    let accumulated = read_reward_per_share(env)
+        .mul(&I256::from_i128(env, shares))
+        .add(&account.correction)
+        .div(&I256::from_i128(env, REWARD_MAGNITUDE))
+        .to_i128()
+        .unwrap();
+
+    accumulated - account.withdrawn
+}
+
+pub fn record_withdrawal(env: &Env, address: Address, amount: i128) {
+    let mut account = read_reward_account(env, address.clone());
+    account.withdrawn += amount;
+    write_reward_account(env, address, &account);
+}
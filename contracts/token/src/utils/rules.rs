@@ -0,0 +1,29 @@
+use crate::{interfaces::TransferRulesClient, storage::DataKey};
+use soroban_sdk::{Address, Env};
+
+pub fn read_transfer_rules(env: &Env) -> Option<Address> {
+    let key = DataKey::TransferRules;
+    env.storage().instance().get(&key)
+}
+
+pub fn write_transfer_rules(env: &Env, rules: &Option<Address>) {
+    let key = DataKey::TransferRules;
+    match rules {
+        Some(rules) => env.storage().instance().set(&key, rules),
+        None => env.storage().instance().remove(&key),
+    }
+}
+
+// Without a registered rules contract every movement is allowed
+pub fn assert_transfer_allowed(
+    env: &Env,
+    from: Option<Address>,
+    to: Option<Address>,
+    amount: i128,
+) {
+    if let Some(rules) = read_transfer_rules(env) {
+        if !TransferRulesClient::new(env, &rules).can_transfer(&from, &to, &amount) {
+            panic!("Transfer rejected by transfer rules");
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{
+    constants::STAKING_REWARD_PRECISION,
+    storage::{DataKey, StakeInfo, StakingPool},
+    utils::ttl::extend_persistent_ttl,
+};
+use soroban_sdk::{Address, Env, I256};
+
+pub fn read_staking_pool(env: &Env) -> StakingPool {
+    let key = DataKey::StakingPool;
+    env.storage()
+        .instance()
+        .get(&key)
+        .unwrap_or_else(|| StakingPool {
+            total_staked: 0,
+            reward_rate: 0,
+            reward_reserve: 0,
+            reward_per_token: I256::from_i32(env, 0),
+            last_update_ledger: env.ledger().sequence(),
+        })
+}
+
+pub fn write_staking_pool(env: &Env, pool: &StakingPool) {
+    let key = DataKey::StakingPool;
+    env.storage().instance().set(&key, pool);
+}
+
+pub fn read_stake(env: &Env, address: Address) -> StakeInfo {
+    let key = DataKey::Stake(address);
+    if let Some(stake) = env.storage().persistent().get::<DataKey, StakeInfo>(&key) {
+        extend_persistent_ttl(env, &key);
+
+        stake
+    } else {
+        StakeInfo {
+            amount: 0,
+            reward_per_token_paid: I256::from_i32(env, 0),
+            rewards: 0,
+            unbonding_amount: 0,
+            unbonding_until_ledger: 0,
+        }
+    }
+}
+
+pub fn write_stake(env: &Env, address: Address, stake: &StakeInfo) {
+    let key = DataKey::Stake(address);
+    if stake.amount == 0 && stake.rewards == 0 && stake.unbonding_amount == 0 {
+        env.storage().persistent().remove(&key);
+        return;
+    }
+
+    env.storage().persistent().set(&key, stake);
+
+    extend_persistent_ttl(env, &key);
+}
+
+// Emits rewards for the ledgers since the last update. Emission pauses while nothing is
+// staked and stops once the reserve runs out.
+pub fn accrue_pool(env: &Env, pool: &mut StakingPool) {
+    let sequence = env.ledger().sequence();
+    if pool.total_staked > 0 {
+        let elapsed = (sequence - pool.last_update_ledger) as i128;
+        let emitted = pool
+            .reward_rate
+            .checked_mul(elapsed)
+            .unwrap_or(i128::MAX)
+            .min(pool.reward_reserve);
+
+        pool.reward_reserve -= emitted;
+        pool.reward_per_token = pool.reward_per_token.add(
+            &I256::from_i128(env, emitted)
+                .mul(&I256::from_i128(env, STAKING_REWARD_PRECISION))
+                .div(&I256::from_i128(env, pool.total_staked)),
+        );
+    }
+    pool.last_update_ledger = sequence;
+}
+
+pub fn accrue_stake(env: &Env, pool: &StakingPool, stake: &mut StakeInfo) {
+    let earned = pool
+        .reward_per_token
+        .sub(&stake.reward_per_token_paid)
+        .mul(&I256::from_i128(env, stake.amount))
+        .div(&I256::from_i128(env, STAKING_REWARD_PRECISION))
+        .to_i128()
+        .unwrap();
+
+    stake.rewards += earned;
+    stake.reward_per_token_paid = pool.reward_per_token.clone();
+}
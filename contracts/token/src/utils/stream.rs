@@ -0,0 +1,55 @@
+use crate::{
+    storage::{DataKey, Stream},
+    utils::ttl::extend_persistent_ttl,
+};
+use soroban_sdk::Env;
+
+pub fn read_stream(env: &Env, id: u32) -> Stream {
+    let key = DataKey::Stream(id);
+    if let Some(stream) = env.storage().persistent().get::<DataKey, Stream>(&key) {
+        extend_persistent_ttl(env, &key);
+
+        stream
+    } else {
+        panic!("Stream not found");
+    }
+}
+
+pub fn write_stream(env: &Env, id: u32, stream: &Stream) {
+    let key = DataKey::Stream(id);
+    env.storage().persistent().set(&key, stream);
+
+    extend_persistent_ttl(env, &key);
+}
+
+pub fn remove_stream(env: &Env, id: u32) {
+    let key = DataKey::Stream(id);
+    env.storage().persistent().remove(&key);
+}
+
+pub fn create_stream(env: &Env, stream: &Stream) -> u32 {
+    let key = DataKey::StreamCount;
+    let id = env.storage().instance().get::<_, u32>(&key).unwrap_or(0);
+    env.storage().instance().set(&key, &(id + 1));
+
+    write_stream(env, id, stream);
+
+    id
+}
+
+// Amount unlocked to the recipient so far, rounded down in the sender's favor.
+// Splitting the deposit into quotient and remainder keeps the math from overflowing.
+pub fn streamed_amount(env: &Env, stream: &Stream) -> i128 {
+    let sequence = env.ledger().sequence();
+    if sequence <= stream.start_ledger {
+        return 0;
+    }
+    if sequence >= stream.stop_ledger {
+        return stream.deposit;
+    }
+
+    let duration = (stream.stop_ledger - stream.start_ledger) as i128;
+    let elapsed = (sequence - stream.start_ledger) as i128;
+
+    stream.deposit / duration * elapsed + stream.deposit % duration * elapsed / duration
+}
@@ -0,0 +1,47 @@
+use crate::{
+    storage::{DataKey, Subscription},
+    utils::ttl::extend_persistent_ttl,
+};
+use soroban_sdk::Env;
+
+pub fn read_subscription(env: &Env, id: u32) -> Subscription {
+    let key = DataKey::Subscription(id);
+    if let Some(subscription) = env
+        .storage()
+        .persistent()
+        .get::<DataKey, Subscription>(&key)
+    {
+        extend_persistent_ttl(env, &key);
+
+        subscription
+    } else {
+        panic!("Subscription not found");
+    }
+}
+
+pub fn write_subscription(env: &Env, id: u32, subscription: &Subscription) {
+    let key = DataKey::Subscription(id);
+    env.storage().persistent().set(&key, subscription);
+
+    extend_persistent_ttl(env, &key);
+}
+
+pub fn remove_subscription(env: &Env, id: u32) {
+    let key = DataKey::Subscription(id);
+    env.storage().persistent().remove(&key);
+}
+
+pub fn create_subscription(env: &Env, subscription: &Subscription) -> u32 {
+    let key = DataKey::SubscriptionCount;
+    let id = env.storage().instance().get::<_, u32>(&key).unwrap_or(0);
+    env.storage().instance().set(&key, &(id + 1));
+
+    write_subscription(env, id, subscription);
+
+    id
+}
+
+// Index of the billing period the current ledger falls into
+pub fn current_period(env: &Env, subscription: &Subscription) -> u32 {
+    (env.ledger().sequence() - subscription.start_ledger) / subscription.period_ledgers
+}
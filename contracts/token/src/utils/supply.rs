@@ -0,0 +1,31 @@
+use crate::{
+    storage::DataKey,
+    utils::{balance::read_balance, rebase::to_amount},
+};
+use soroban_sdk::Env;
+
+// Total shares of all balances except the escrow balance, which is stored as an amount
+pub fn read_total_shares(env: &Env) -> i128 {
+    let key = DataKey::TotalSupply;
+    env.storage().instance().get(&key).unwrap_or(0)
+}
+
+fn write_total_shares(env: &Env, shares: i128) {
+    let key = DataKey::TotalSupply;
+    env.storage().instance().set(&key, &shares);
+}
+
+pub fn read_total_supply(env: &Env) -> i128 {
+    to_amount(env, read_total_shares(env)) + read_balance(env, env.current_contract_address())
+}
+
+pub fn increase_total_shares(env: &Env, shares: i128) {
+    let total_shares = read_total_shares(env)
+        .checked_add(shares)
+        .unwrap_or_else(|| panic!("Total supply overflow"));
+    write_total_shares(env, total_shares);
+}
+
+pub fn decrease_total_shares(env: &Env, shares: i128) {
+    write_total_shares(env, read_total_shares(env) - shares);
+}
@@ -0,0 +1,71 @@
+use crate::utils::{
+    balance::{decrease_balance, increase_balance, transfer_balance},
+    freeze::{assert_account_not_frozen, assert_frozen_amount_kept},
+    rules::assert_transfer_allowed,
+    ttl::extend_instance_ttl,
+};
+use soroban_sdk::{Address, Env};
+use soroban_token_sdk::TokenUtils;
+
+// Every balance-moving path goes through `move_balance`, so policies are enforced in one place
+pub enum Movement {
+    Transfer { from: Address, to: Address },
+    Mint { issuer: Address, to: Address },
+    Burn { from: Address },
+}
+
+pub fn assert_nonnegative_amount(amount: i128) {
+    if amount < 0 {
+        panic!("Negative amount is not allowed: {}", amount);
+    }
+}
+
+// Policy checks that must pass before any balance is touched
+fn before_move(env: &Env, movement: &Movement, amount: i128) {
+    assert_nonnegative_amount(amount);
+
+    match movement {
+        Movement::Transfer { from, .. } | Movement::Burn { from } => {
+            assert_account_not_frozen(env, from);
+            assert_frozen_amount_kept(env, from, amount);
+        }
+        Movement::Mint { .. } => {}
+    }
+
+    let (from, to) = match movement {
+        Movement::Transfer { from, to } => (Some(from.clone()), Some(to.clone())),
+        Movement::Mint { to, .. } => (None, Some(to.clone())),
+        Movement::Burn { from } => (Some(from.clone()), None),
+    };
+    assert_transfer_allowed(env, from, to, amount);
+}
+
+// Runs once balances and the total supply are updated
+fn after_move(env: &Env, movement: Movement, amount: i128) {
+    let events = TokenUtils::new(env).events();
+    match movement {
+        Movement::Transfer { from, to } => events.transfer(from, to, amount),
+        Movement::Mint { issuer, to } => events.mint(issuer, to, amount),
+        Movement::Burn { from } => events.burn(from, amount),
+    }
+}
+
+pub fn move_balance(env: &Env, movement: Movement, amount: i128) {
+    before_move(env, &movement, amount);
+
+    extend_instance_ttl(env);
+
+    match &movement {
+        Movement::Transfer { from, to } => {
+            transfer_balance(env, from.clone(), to.clone(), amount);
+        }
+        Movement::Mint { to, .. } => {
+            increase_balance(env, to.clone(), amount);
+        }
+        Movement::Burn { from } => {
+            decrease_balance(env, from.clone(), amount);
+        }
+    }
+
+    after_move(env, movement, amount);
+}
@@ -0,0 +1,71 @@
+use crate::{
+    constants::{
+        BALANCE_TTL_FULL, BALANCE_TTL_THRESHOLD, INSTANCE_TTL_FULL, INSTANCE_TTL_THRESHOLD,
+    },
+    storage::{DataKey, TtlPolicy},
+};
+use soroban_sdk::{Address, Env, IntoVal, Val};
+
+pub fn read_ttl_policy(env: &Env) -> TtlPolicy {
+    let key = DataKey::TtlPolicy;
+    env.storage().instance().get(&key).unwrap_or(TtlPolicy {
+        instance_ttl_threshold: INSTANCE_TTL_THRESHOLD,
+        instance_ttl_full: INSTANCE_TTL_FULL,
+        balance_ttl_threshold: BALANCE_TTL_THRESHOLD,
+        balance_ttl_full: BALANCE_TTL_FULL,
+    })
+}
+
+pub fn write_ttl_policy(env: &Env, policy: &TtlPolicy) {
+    let max_ttl = env.storage().max_ttl();
+
+    if policy.instance_ttl_threshold >= policy.instance_ttl_full
+        || policy.balance_ttl_threshold >= policy.balance_ttl_full
+    {
+        panic!("TTL threshold must be less than the full TTL");
+    }
+
+    if policy.instance_ttl_full > max_ttl || policy.balance_ttl_full > max_ttl {
+        panic!("Full TTL can't be greater than the network's maximum TTL");
+    }
+
+    let key = DataKey::TtlPolicy;
+    env.storage().instance().set(&key, policy);
+}
+
+pub fn extend_instance_ttl(env: &Env) {
+    let policy = read_ttl_policy(env);
+    env.storage()
+        .instance()
+        .extend_ttl(policy.instance_ttl_threshold, policy.instance_ttl_full);
+}
+
+// Balances and other per-account entries in persistent storage share the balance TTL
+pub fn extend_persistent_ttl<K>(env: &Env, key: &K)
+where
+    K: IntoVal<Env, Val>,
+{
+    let policy = read_ttl_policy(env);
+    env.storage().persistent().extend_ttl(
+        key,
+        policy.balance_ttl_threshold,
+        policy.balance_ttl_full,
+    );
+}
+
+// Extends the other per-account entries a dormant holder depends on, where they exist
+pub fn extend_account_ttl(env: &Env, account: &Address) {
+    let keys = [
+        DataKey::Stake(account.clone()),
+        DataKey::Rewards(account.clone()),
+        DataKey::RewardExcluded(account.clone()),
+        DataKey::Spenders(account.clone()),
+        DataKey::FrozenAmount(account.clone()),
+    ];
+
+    for key in keys {
+        if env.storage().persistent().has(&key) {
+            extend_persistent_ttl(env, &key);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use crate::{storage::DataKey, utils::supply::read_total_supply};
+use soroban_sdk::{token, Address, Env};
+
+pub fn read_underlying(env: &Env) -> Option<Address> {
+    let key = DataKey::Underlying;
+    env.storage().instance().get(&key)
+}
+
+pub fn write_underlying(env: &Env, underlying: &Address) {
+    let key = DataKey::Underlying;
+    env.storage().instance().set(&key, underlying);
+}
+
+// In wrapped-asset mode every token must be backed by the underlying held by this contract
+pub fn assert_fully_backed(env: &Env) {
+    if let Some(underlying) = read_underlying(env) {
+        let reserve = token::Client::new(env, &underlying).balance(&env.current_contract_address());
+        if read_total_supply(env) > reserve {
+            panic!("Total supply exceeds underlying reserve");
+        }
+    }
+}